@@ -0,0 +1,183 @@
+use crate::parse::{Expr, Function, Statement};
+
+/// 構文木をJSONに変換する
+/// 各ノードは種類を表す"type"と，その種類ごとのフィールドを持つオブジェクトになる
+pub fn to_json(statements: &[Statement]) -> String {
+    statements_to_json(statements)
+}
+
+fn statements_to_json(statements: &[Statement]) -> String {
+    array(statements.iter().map(statement_to_json))
+}
+
+fn statement_to_json(statement: &Statement) -> String {
+    match statement {
+        Statement::Return(expr) => node("Return", &[("value", expr_to_json(expr))]),
+        Statement::Print(arguments) => node("Print", &[("arguments", exprs_to_json(arguments))]),
+        Statement::Eprint(arguments) => node("Eprint", &[("arguments", exprs_to_json(arguments))]),
+        Statement::Expr(expr) => node("Expr", &[("expr", expr_to_json(expr))]),
+        Statement::Block(statements) => node("Block", &[("statements", statements_to_json(statements))]),
+        Statement::If { condition, block, else_block } => node(
+            "If",
+            &[
+                ("condition", expr_to_json(condition)),
+                ("block", statement_to_json(block)),
+                ("else_block", optional(else_block.as_deref().map(statement_to_json))),
+            ],
+        ),
+        Statement::While { condition, block, else_block } => node(
+            "While",
+            &[
+                ("condition", expr_to_json(condition)),
+                ("block", statement_to_json(block)),
+                ("else_block", optional(else_block.as_deref().map(statement_to_json))),
+            ],
+        ),
+        Statement::WhileLet { name, value, block } => node(
+            "WhileLet",
+            &[
+                ("name", string(name)),
+                ("value", expr_to_json(value)),
+                ("block", statement_to_json(block)),
+            ],
+        ),
+        Statement::Break(value) => node("Break", &[("value", optional(value.as_deref().map(expr_to_json)))]),
+        Statement::Continue => node("Continue", &[]),
+        Statement::Loop(block) => node("Loop", &[("block", statement_to_json(block))]),
+        Statement::Function { name, function } => node(
+            "Function",
+            &[("name", string(name)), ("function", function_to_json(function))],
+        ),
+        Statement::Include(path) => node("Include", &[("path", string(path))]),
+        Statement::TryCatch { try_block, name, catch_block } => node(
+            "TryCatch",
+            &[
+                ("try_block", statement_to_json(try_block)),
+                ("name", string(name)),
+                ("catch_block", statement_to_json(catch_block)),
+            ],
+        ),
+    }
+}
+
+fn exprs_to_json(exprs: &[Expr]) -> String {
+    array(exprs.iter().map(expr_to_json))
+}
+
+fn expr_to_json(expr: &Expr) -> String {
+    match expr {
+        Expr::Identifier(name) => node("Identifier", &[("name", string(name))]),
+        Expr::Local(index) => node("Local", &[("index", index.to_string())]),
+        Expr::Number(n) => node("Number", &[("value", number(*n))]),
+        Expr::String(s) => node("String", &[("value", string(&s.value))]),
+        Expr::Char(c) => node("Char", &[("value", string(&c.to_string()))]),
+        Expr::Null => node("Null", &[]),
+        Expr::Boolean(b) => node("Boolean", &[("value", b.to_string())]),
+        Expr::PrefixExpr { operator, right } => node(
+            "PrefixExpr",
+            &[("operator", string(&format!("{:?}", operator))), ("right", expr_to_json(right))],
+        ),
+        Expr::InfixExpr { left, operator, right } => node(
+            "InfixExpr",
+            &[
+                ("left", expr_to_json(left)),
+                ("operator", string(&format!("{:?}", operator))),
+                ("right", expr_to_json(right)),
+            ],
+        ),
+        Expr::PostfixExpr { left, operator } => node(
+            "PostfixExpr",
+            &[("left", expr_to_json(left)), ("operator", string(&format!("{:?}", operator)))],
+        ),
+        Expr::Typeof(operand) => node("Typeof", &[("operand", expr_to_json(operand))]),
+        Expr::Array(elements) => node("Array", &[("elements", exprs_to_json(elements))]),
+        Expr::Tuple(elements) => node("Tuple", &[("elements", exprs_to_json(elements))]),
+        Expr::Loop(block) => node("Loop", &[("block", statement_to_json(block))]),
+        Expr::Function(function) => function_to_json(function),
+        Expr::Call { function, arguments } => node(
+            "Call",
+            &[("function", expr_to_json(function)), ("arguments", exprs_to_json(arguments))],
+        ),
+        Expr::Index { left, index } => node(
+            "Index",
+            &[("left", expr_to_json(left)), ("index", expr_to_json(index))],
+        ),
+        Expr::Object(fields) => {
+            let fields = fields
+                .iter()
+                .map(|(name, value)| format!("{{\"name\":{},\"value\":{}}}", string(name), expr_to_json(value)));
+            node("Object", &[("fields", array(fields))])
+        }
+        Expr::Field { left, name } => node(
+            "Field",
+            &[("left", expr_to_json(left)), ("name", string(name))],
+        ),
+        Expr::Spread(array) => node("Spread", &[("array", expr_to_json(array))]),
+        Expr::Conditional { condition, then_value, else_value } => node(
+            "Conditional",
+            &[
+                ("condition", expr_to_json(condition)),
+                ("then_value", expr_to_json(then_value)),
+                ("else_value", expr_to_json(else_value)),
+            ],
+        ),
+    }
+}
+
+fn function_to_json(function: &Function) -> String {
+    node(
+        "FunctionLiteral",
+        &[
+            ("name", optional(function.name.as_deref().map(string))),
+            ("parameters", array(function.parameters.iter().map(|parameter| string(parameter)))),
+            ("body", statements_to_json(&function.body)),
+            ("pure", function.pure.to_string()),
+        ],
+    )
+}
+
+/// 種類がkindで，fieldsを持つノード
+fn node(kind: &str, fields: &[(&str, String)]) -> String {
+    let mut json = format!("{{\"type\":{}", string(kind));
+    for (name, value) in fields {
+        json.push_str(&format!(",{}:{}", string(name), value));
+    }
+    json.push('}');
+
+    json
+}
+
+fn array(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
+
+fn optional(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "null".to_string())
+}
+
+/// JSONでは無限大とNaNを表せないため，nullにする
+fn number(n: f64) -> String {
+    if n.is_finite() {
+        n.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+fn string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+
+    json
+}
@@ -0,0 +1,1079 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::env;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::interpreter::{values_equal, Interpreter, RuntimeError};
+use crate::parse::Function;
+use crate::types::Primitive;
+
+/// f64で正確に表せる最大の整数
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+/// 組み込み関数
+pub type Builtin = fn(&[Primitive]) -> Result<Primitive, RuntimeError>;
+
+/// インタプリタに登録する組み込み関数
+pub const BUILTINS: &[(&str, Builtin)] = &[
+    ("clamp", clamp),
+    ("round", round),
+    ("floor", floor),
+    ("ceil", ceil),
+    ("trunc", trunc),
+    ("fract", fract),
+    ("to_fixed", to_fixed),
+    ("to_base", to_base),
+    ("abs", abs),
+    ("sign", sign),
+    ("isnan", isnan),
+    ("isinf", isinf),
+    ("gcd", gcd),
+    ("lcm", lcm),
+    ("env", env_var),
+    ("time_ms", time_ms),
+    ("sleep", sleep),
+    ("push", push),
+    ("pop", pop),
+    ("slice", slice),
+    ("index_of", index_of),
+    ("contains", contains),
+    ("count", count),
+    ("pad_start", pad_start),
+    ("pad_end", pad_end),
+    ("repeat", repeat),
+    ("reverse", reverse),
+    ("error", error),
+    ("char_at", char_at),
+    ("ord", ord),
+    ("chr", chr),
+    ("len", len),
+    ("deep_equal", deep_equal),
+];
+
+/// 関数を引数にとる組み込み関数
+pub type HigherOrderBuiltin = fn(&mut Interpreter, &[Primitive]) -> Result<Primitive, RuntimeError>;
+
+/// インタプリタを必要とする組み込み関数
+pub const HIGHER_ORDER_BUILTINS: &[(&str, HigherOrderBuiltin)] = &[
+    ("map", map),
+    ("filter", filter),
+    ("reduce", reduce),
+    ("sort", sort),
+    ("min", min),
+    ("max", max),
+    ("help", help),
+];
+
+/// 型ごとにメソッドとして呼び出せる組み込み関数
+/// `value.name(args)` は `name(value, args)` として呼び出す
+pub const METHODS: &[(&str, &[&str])] = &[
+    ("number", &["abs", "round", "floor", "ceil", "trunc", "fract", "to_fixed", "to_base", "sign", "clamp", "isnan", "isinf", "gcd", "lcm", "chr", "min", "max"]),
+    ("string", &["len", "count", "char_at", "ord", "pad_start", "pad_end", "repeat", "reverse", "min", "max"]),
+    ("char", &["ord", "min", "max"]),
+    (
+        "array",
+        &["len", "push", "pop", "slice", "index_of", "contains", "count", "repeat", "reverse", "map", "filter", "reduce", "sort", "deep_equal"],
+    ),
+    ("object", &["len", "deep_equal"]),
+];
+
+/// type_nameの型の値がnameのメソッドを持つかどうかを返す
+pub fn has_method(type_name: &str, name: &str) -> bool {
+    METHODS
+        .iter()
+        .any(|(methods_type, methods)| *methods_type == type_name && methods.contains(&name))
+}
+
+/// 名前に対応する関数を引数にとる組み込み関数を返す
+pub fn lookup_higher_order(name: &str) -> Option<HigherOrderBuiltin> {
+    HIGHER_ORDER_BUILTINS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, builtin)| *builtin)
+}
+
+/// help()
+/// 組み込み関数の名前と，定義済みの関数の名前と仮引数を返す
+fn help(interpreter: &mut Interpreter, args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("help", args, 0)?;
+
+    let builtins = interpreter.builtin_names().join(", ");
+    let functions = interpreter
+        .globals()
+        .into_iter()
+        .filter_map(|(name, value)| match value {
+            Primitive::Function(function) => Some(format!("{}({})", name, function.parameters.join(", "))),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Ok(Primitive::String(Rc::new(format!("builtins: {}\nfunctions: {}", builtins, functions))))
+}
+
+/// map(array, function)
+/// 各要素にfunctionを適用した新しい配列を返す
+fn map(interpreter: &mut Interpreter, args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("map", args, 2)?;
+    let array = expect_array("map", &args[0])?;
+    let function = expect_function("map", &args[1])?;
+
+    let elements = array.borrow().clone();
+    let mapped = elements
+        .into_iter()
+        .map(|element| interpreter.call_function(&function, vec![element]))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(mapped.into())
+}
+
+/// filter(array, function)
+/// functionがtrueを返した要素からなる新しい配列を返す
+fn filter(interpreter: &mut Interpreter, args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("filter", args, 2)?;
+    let array = expect_array("filter", &args[0])?;
+    let function = expect_function("filter", &args[1])?;
+
+    let elements = array.borrow().clone();
+    let mut filtered = Vec::new();
+    for element in elements {
+        match interpreter.call_function(&function, vec![element.clone()])? {
+            Primitive::Boolean(true) => filtered.push(element),
+            Primitive::Boolean(false) => (),
+            value => {
+                return Err(RuntimeError::TypeMismatch(format!(
+                    "filter: callback must return boolean, found {}",
+                    value
+                )))
+            }
+        }
+    }
+
+    Ok(filtered.into())
+}
+
+/// reduce(array, function, initial)
+/// initialを初期値として，各要素にfunction(acc, x)を順に適用した結果を返す
+fn reduce(interpreter: &mut Interpreter, args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("reduce", args, 3)?;
+    let array = expect_array("reduce", &args[0])?;
+    let function = expect_function("reduce", &args[1])?;
+
+    let elements = array.borrow().clone();
+    elements.into_iter().try_fold(args[2].clone(), |acc, element| {
+        interpreter.call_function(&function, vec![acc, element])
+    })
+}
+
+/// sort(array) / sort(array, compare)
+/// 昇順に並べた新しい配列を返す
+/// compare(a, b)は，aを先にするなら負，bを先にするなら正の数を返す
+fn sort(interpreter: &mut Interpreter, args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let (array, compare) = match args {
+        [array] => (expect_array("sort", array)?, None),
+        [array, compare] => (expect_array("sort", array)?, Some(expect_function("sort", compare)?)),
+        _ => return Err(argument_count_range("sort", args, 1, 2)),
+    };
+
+    let mut elements = array.borrow().clone();
+
+    // sort_byの比較関数からはエラーを返せないため，最初のエラーを覚えておく
+    let mut error = None;
+    elements.sort_by(|a, b| {
+        if error.is_some() {
+            return Ordering::Equal;
+        }
+
+        compare_with(interpreter, "sort", compare.as_ref(), a, b).unwrap_or_else(|e| {
+            error = Some(e);
+            Ordering::Equal
+        })
+    });
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok(elements.into()),
+    }
+}
+
+/// min(a, b) / min(a, b, compare)
+/// 小さい方を返す (等しければa)
+fn min(interpreter: &mut Interpreter, args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let (a, b, compare) = expect_min_max_args("min", args)?;
+    match compare_with(interpreter, "min", compare.as_ref(), a, b)? {
+        Ordering::Greater => Ok(b.clone()),
+        _ => Ok(a.clone()),
+    }
+}
+
+/// max(a, b) / max(a, b, compare)
+/// 大きい方を返す (等しければa)
+fn max(interpreter: &mut Interpreter, args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let (a, b, compare) = expect_min_max_args("max", args)?;
+    match compare_with(interpreter, "max", compare.as_ref(), a, b)? {
+        Ordering::Less => Ok(b.clone()),
+        _ => Ok(a.clone()),
+    }
+}
+
+/// min，maxの引数を比較する2つの値と比較関数に分ける
+fn expect_min_max_args<'a>(
+    name: &str,
+    args: &'a [Primitive],
+) -> Result<(&'a Primitive, &'a Primitive, Option<Rc<Function>>), RuntimeError> {
+    match args {
+        [a, b] => Ok((a, b, None)),
+        [a, b, compare] => Ok((a, b, Some(expect_function(name, compare)?))),
+        _ => Err(argument_count_range(name, args, 2, 3)),
+    }
+}
+
+/// compareがあればcompare(a, b)の符号で，なければ値そのもので比較する
+fn compare_with(
+    interpreter: &mut Interpreter,
+    name: &str,
+    compare: Option<&Rc<Function>>,
+    a: &Primitive,
+    b: &Primitive,
+) -> Result<Ordering, RuntimeError> {
+    match compare {
+        Some(compare) => match interpreter.call_function(compare, vec![a.clone(), b.clone()])? {
+            Primitive::Number(n) => Ok(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+            result => Err(RuntimeError::TypeMismatch(format!(
+                "{}: comparator must return number, found {}",
+                name, result
+            ))),
+        },
+        None => compare_values(name, a, b),
+    }
+}
+
+/// 同じ型の数値，文字列，文字を比較する
+fn compare_values(name: &str, a: &Primitive, b: &Primitive) -> Result<Ordering, RuntimeError> {
+    match (a, b) {
+        (Primitive::Number(a), Primitive::Number(b)) => Ok(a.total_cmp(b)),
+        (Primitive::String(a), Primitive::String(b)) => Ok(a.cmp(b)),
+        (Primitive::Char(a), Primitive::Char(b)) => Ok(a.cmp(b)),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "{}: cannot compare {} and {}",
+            name, a, b
+        ))),
+    }
+}
+
+/// clamp(value, low, high)
+/// valueを[low, high]の範囲に収める
+fn clamp(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("clamp", args, 3)?;
+    let value = expect_number("clamp", &args[0])?;
+    let low = expect_number("clamp", &args[1])?;
+    let high = expect_number("clamp", &args[2])?;
+
+    if low.is_nan() || high.is_nan() || low > high {
+        return Err(RuntimeError::InvalidArgument(format!(
+            "clamp: low ({}) must not be greater than high ({})",
+            low, high
+        )));
+    }
+
+    Ok(Primitive::Number(value.clamp(low, high)))
+}
+
+/// round(x) / round(x, digits)
+/// xを整数，またはdigits桁の小数に丸める
+fn round(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    match args {
+        [x] => Ok(Primitive::Number(expect_number("round", x)?.round())),
+        [x, digits] => {
+            let x = expect_number("round", x)?;
+            let digits = expect_non_negative_integer("round", digits)?;
+
+            // to_fixedと同じく，桁が大きすぎて丸められなければそのまま返す
+            let scale = 10f64.powi(digits as i32);
+            let rounded = (x * scale).round() / scale;
+            Ok(Primitive::Number(if rounded.is_finite() { rounded } else { x }))
+        }
+        _ => Err(argument_count_range("round", args, 1, 2)),
+    }
+}
+
+/// floor(x)
+/// x以下の最大の整数を返す
+fn floor(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("floor", args, 1)?;
+    Ok(Primitive::Number(expect_number("floor", &args[0])?.floor()))
+}
+
+/// ceil(x)
+/// x以上の最小の整数を返す
+fn ceil(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("ceil", args, 1)?;
+    Ok(Primitive::Number(expect_number("ceil", &args[0])?.ceil()))
+}
+
+/// trunc(x)
+/// xの小数部分を切り捨てた(0の方向に丸めた)整数を返す
+fn trunc(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("trunc", args, 1)?;
+    Ok(Primitive::Number(expect_number("trunc", &args[0])?.trunc()))
+}
+
+/// fract(x)
+/// xの小数部分 (x - trunc(x)) を返す
+fn fract(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("fract", args, 1)?;
+    Ok(Primitive::Number(expect_number("fract", &args[0])?.fract()))
+}
+
+/// to_fixed(x, digits)
+/// xを小数点以下digits桁に丸めた文字列を返す
+/// roundと同じく，ちょうど中間の値は0から遠い方に丸める
+fn to_fixed(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("to_fixed", args, 2)?;
+    let x = expect_number("to_fixed", &args[0])?;
+    let digits = expect_non_negative_integer("to_fixed", &args[1])?;
+
+    // 桁が大きすぎて丸められなければ，そのまま書式化する
+    let scale = 10f64.powi(digits as i32);
+    let rounded = (x * scale).round() / scale;
+    let x = if rounded.is_finite() { rounded } else { x };
+
+    Ok(Primitive::String(Rc::new(format!("{:.*}", digits, x))))
+}
+
+/// to_base(n, base)
+/// 整数nをbase進数(2から36)で表した文字列を返す (10以上の桁は小文字の英字)
+fn to_base(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("to_base", args, 2)?;
+    let n = expect_integer("to_base", &args[0])?;
+    let base = expect_integer("to_base", &args[1])?;
+    if !(2..=36).contains(&base) {
+        return Err(RuntimeError::InvalidArgument(format!(
+            "to_base: base must be between 2 and 36, found {}",
+            base
+        )));
+    }
+
+    let mut digits = Vec::new();
+    let mut rest = n.unsigned_abs();
+    loop {
+        digits.push(std::char::from_digit((rest % base as u64) as u32, base as u32).unwrap());
+        rest /= base as u64;
+        if rest == 0 {
+            break;
+        }
+    }
+    if n < 0 {
+        digits.push('-');
+    }
+
+    Ok(Primitive::String(Rc::new(digits.iter().rev().collect())))
+}
+
+/// abs(x)
+/// xの絶対値を返す
+fn abs(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("abs", args, 1)?;
+    Ok(Primitive::Number(expect_number("abs", &args[0])?.abs()))
+}
+
+/// sign(x)
+/// xの符号を-1, 0, 1で返す
+fn sign(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("sign", args, 1)?;
+    let x = expect_number("sign", &args[0])?;
+
+    let sign = if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        0.0
+    };
+    Ok(Primitive::Number(sign))
+}
+
+/// isnan(x)
+/// xがNaNかどうかを返す
+fn isnan(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("isnan", args, 1)?;
+    Ok(Primitive::Boolean(expect_number("isnan", &args[0])?.is_nan()))
+}
+
+/// isinf(x)
+/// xが正または負の無限大かどうかを返す
+fn isinf(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("isinf", args, 1)?;
+    Ok(Primitive::Boolean(expect_number("isinf", &args[0])?.is_infinite()))
+}
+
+/// gcd(a, b)
+/// 最大公約数を返す
+fn gcd(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("gcd", args, 2)?;
+    let a = expect_integer("gcd", &args[0])?;
+    let b = expect_integer("gcd", &args[1])?;
+
+    Ok(Primitive::Number(euclid(a, b) as f64))
+}
+
+/// lcm(a, b)
+/// 最小公倍数を返す
+fn lcm(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("lcm", args, 2)?;
+    let a = expect_integer("lcm", &args[0])?;
+    let b = expect_integer("lcm", &args[1])?;
+
+    if a == 0 || b == 0 {
+        return Ok(Primitive::Number(0.0));
+    }
+
+    // 結果がf64で正確に表せなければエラーにする
+    (a / euclid(a, b))
+        .unsigned_abs()
+        .checked_mul(b.unsigned_abs())
+        .filter(|lcm| *lcm <= MAX_SAFE_INTEGER as u64)
+        .map(|lcm| Primitive::Number(lcm as f64))
+        .ok_or_else(|| RuntimeError::IntegerOverflow(format!("lcm({}, {})", a, b)))
+}
+
+/// ユークリッドの互除法
+fn euclid(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// env(name)
+/// 環境変数の値を文字列で返す
+/// 未設定(または値がUnicodeでない)場合は空文字列を返す
+fn env_var(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("env", args, 1)?;
+    let name = expect_string("env", &args[0])?;
+
+    let value = env::var(name.as_str()).unwrap_or_default();
+    Ok(Primitive::String(Rc::new(value)))
+}
+
+/// time_ms()
+/// UNIXエポックからの経過時間をミリ秒で返す
+fn time_ms(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("time_ms", args, 0)?;
+
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(Primitive::Number(elapsed.as_millis() as f64))
+}
+
+/// sleep(ms)
+/// msミリ秒だけ実行を止める
+fn sleep(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("sleep", args, 1)?;
+    let ms = expect_non_negative_integer("sleep", &args[0])?;
+
+    thread::sleep(Duration::from_millis(ms as u64));
+    Ok(Primitive::Null)
+}
+
+/// push(array, value)
+/// 配列の末尾に値を追加し，追加後の長さを返す
+fn push(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("push", args, 2)?;
+    let array = expect_array("push", &args[0])?;
+
+    let mut array = array.borrow_mut();
+    array.push(args[1].clone());
+    Ok(Primitive::Number(array.len() as f64))
+}
+
+/// pop(array)
+/// 配列の末尾の要素を取り除いて返す
+fn pop(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("pop", args, 1)?;
+    let array = expect_array("pop", &args[0])?;
+
+    let value = array.borrow_mut().pop();
+    value.ok_or_else(|| RuntimeError::InvalidArgument("pop: array is empty".to_string()))
+}
+
+/// slice(array, start, end)
+/// 配列の[start, end)の要素からなる新しい配列を返す
+fn slice(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("slice", args, 3)?;
+    let array = expect_array("slice", &args[0])?;
+    let start = expect_non_negative_integer("slice", &args[1])?;
+    let end = expect_non_negative_integer("slice", &args[2])?;
+
+    let array = array.borrow();
+    if start > end || end > array.len() {
+        return Err(RuntimeError::InvalidArgument(format!(
+            "slice: range {}..{} out of bounds for length {}",
+            start, end, array.len()
+        )));
+    }
+
+    Ok(array[start..end].to_vec().into())
+}
+
+/// index_of(array, value)
+/// valueと等しい(==)最初の要素の添字を返す
+/// 見つからなければ-1を返す
+fn index_of(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("index_of", args, 2)?;
+    let array = expect_array("index_of", &args[0])?;
+
+    let index = array.borrow().iter().position(|element| values_equal(element, &args[1]));
+    Ok(Primitive::Number(index.map_or(-1.0, |index| index as f64)))
+}
+
+/// contains(array, value)
+/// valueと等しい(==)要素があるかどうかを返す
+fn contains(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("contains", args, 2)?;
+    let array = expect_array("contains", &args[0])?;
+
+    let found = array.borrow().iter().any(|element| values_equal(element, &args[1]));
+    Ok(Primitive::Boolean(found))
+}
+
+/// count(string, substring) / count(array, value)
+/// 文字列なら重ならない部分文字列の数を，配列ならvalueと等しい(==)要素の数を返す
+fn count(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("count", args, 2)?;
+
+    let count = match (&args[0], &args[1]) {
+        (Primitive::String(_), Primitive::String(substring)) if substring.is_empty() => {
+            return Err(RuntimeError::InvalidArgument("count: substring must not be empty".to_string()))
+        }
+        (Primitive::String(s), Primitive::String(substring)) => s.matches(substring.as_str()).count(),
+        (Primitive::String(s), Primitive::Char(c)) => s.matches(*c).count(),
+        (Primitive::String(_), value) => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "count: expected string or char to search for, found {}",
+                value
+            )))
+        }
+        (Primitive::Array(array), value) => array.borrow().iter().filter(|element| values_equal(element, value)).count(),
+        (value, _) => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "count: expected string or array, found {}",
+                value
+            )))
+        }
+    };
+
+    Ok(Primitive::Number(count as f64))
+}
+
+/// pad_start(value, width)
+/// valueを文字列にし，width文字になるまで先頭を空白で埋める(右寄せ)
+fn pad_start(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("pad_start", args, 2)?;
+    let width = expect_non_negative_integer("pad_start", &args[1])?;
+
+    Ok(Primitive::String(Rc::new(format!("{:>width$}", args[0].to_string()))))
+}
+
+/// pad_end(value, width)
+/// valueを文字列にし，width文字になるまで末尾を空白で埋める(左寄せ)
+fn pad_end(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("pad_end", args, 2)?;
+    let width = expect_non_negative_integer("pad_end", &args[1])?;
+
+    Ok(Primitive::String(Rc::new(format!("{:<width$}", args[0].to_string()))))
+}
+
+/// repeat(value, count)
+/// 文字列または配列をcount回繰り返した新しい値を返す
+fn repeat(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("repeat", args, 2)?;
+    let count = expect_non_negative_integer("repeat", &args[1])?;
+
+    match &args[0] {
+        Primitive::String(s) => Ok(Primitive::String(Rc::new(s.repeat(count)))),
+        Primitive::Array(a) => {
+            let a = a.borrow();
+            Ok(a.iter().cycle().take(a.len() * count).cloned().collect::<Vec<_>>().into())
+        }
+        value => Err(RuntimeError::TypeMismatch(format!(
+            "repeat: expected string or array, found {}",
+            value
+        ))),
+    }
+}
+
+/// reverse(value)
+/// 文字列(文字単位)または配列を逆順にした新しい値を返す
+fn reverse(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("reverse", args, 1)?;
+
+    match &args[0] {
+        Primitive::String(s) => Ok(Primitive::String(Rc::new(s.chars().rev().collect()))),
+        Primitive::Array(a) => Ok(a.borrow().iter().rev().cloned().collect::<Vec<_>>().into()),
+        value => Err(RuntimeError::TypeMismatch(format!(
+            "reverse: expected string or array, found {}",
+            value
+        ))),
+    }
+}
+
+/// error(message)
+/// messageを持つ実行時エラーを発生させる
+fn error(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("error", args, 1)?;
+    let message = expect_string("error", &args[0])?;
+    Err(RuntimeError::UserError(message.to_string()))
+}
+
+/// char_at(string, index)
+/// index番目(文字単位)の文字を返す
+fn char_at(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("char_at", args, 2)?;
+    let s = expect_string("char_at", &args[0])?;
+    let index = expect_non_negative_integer("char_at", &args[1])?;
+
+    s.chars().nth(index).map(Primitive::Char).ok_or_else(|| RuntimeError::IndexOutOfRange {
+        index: index as f64,
+        length: s.chars().count(),
+    })
+}
+
+/// ord(char)
+/// 文字(または1文字の文字列)のUnicodeのコードポイントを返す
+fn ord(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("ord", args, 1)?;
+
+    let c = match &args[0] {
+        Primitive::Char(c) => *c,
+        Primitive::String(s) if s.chars().count() == 1 => s.chars().next().unwrap(),
+        value => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "ord: expected single character, found {}",
+                value
+            )))
+        }
+    };
+
+    Ok(Primitive::Number(c as u32 as f64))
+}
+
+/// chr(code_point)
+/// Unicodeのコードポイントに対応する文字を返す
+fn chr(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("chr", args, 1)?;
+    let code_point = expect_integer("chr", &args[0])?;
+
+    u32::try_from(code_point)
+        .ok()
+        .and_then(char::from_u32)
+        .map(Primitive::Char)
+        .ok_or_else(|| RuntimeError::InvalidArgument(format!("chr: invalid code point {}", code_point)))
+}
+
+/// len(value)
+/// 文字列の文字数，配列の要素数，オブジェクトのフィールド数を返す
+fn len(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("len", args, 1)?;
+
+    let length = match &args[0] {
+        Primitive::String(s) => s.chars().count(),
+        Primitive::Array(a) => a.borrow().len(),
+        Primitive::Object(object) => object.borrow().len(),
+        value => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "len: expected string, array or object, found {}",
+                value
+            )))
+        }
+    };
+
+    Ok(Primitive::Number(length as f64))
+}
+
+/// deep_equal(a, b)
+/// 配列，タプルは要素ごとに，オブジェクトはフィールドごとに再帰的に比較する (==と同じ)
+fn deep_equal(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    expect_arity("deep_equal", args, 2)?;
+    Ok(Primitive::Boolean(values_equal(&args[0], &args[1])))
+}
+
+/// 引数の数を検査する
+fn expect_arity(name: &str, args: &[Primitive], expected: usize) -> Result<(), RuntimeError> {
+    if args.len() == expected {
+        Ok(())
+    } else {
+        Err(RuntimeError::ArgumentCount {
+            name: name.to_string(),
+            expected,
+            actual: args.len(),
+        })
+    }
+}
+
+/// 引数の数がminからmaxの間でないことを示すエラー
+fn argument_count_range(name: &str, args: &[Primitive], min: usize, max: usize) -> RuntimeError {
+    RuntimeError::ArgumentCountRange {
+        name: name.to_string(),
+        min,
+        max,
+        actual: args.len(),
+    }
+}
+
+/// 引数が数値であることを検査する
+fn expect_number(name: &str, arg: &Primitive) -> Result<f64, RuntimeError> {
+    match arg {
+        Primitive::Number(n) => Ok(*n),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "{}: expected number, found {}",
+            name, arg
+        ))),
+    }
+}
+
+/// 引数が文字列であることを検査する
+fn expect_string(name: &str, arg: &Primitive) -> Result<Rc<String>, RuntimeError> {
+    match arg {
+        Primitive::String(s) => Ok(Rc::clone(s)),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "{}: expected string, found {}",
+            name, arg
+        ))),
+    }
+}
+
+/// 引数が配列であることを検査する
+fn expect_array(name: &str, arg: &Primitive) -> Result<Rc<RefCell<Vec<Primitive>>>, RuntimeError> {
+    match arg {
+        Primitive::Array(a) => Ok(Rc::clone(a)),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "{}: expected array, found {}",
+            name, arg
+        ))),
+    }
+}
+
+/// 引数が整数であることを検査する
+fn expect_integer(name: &str, arg: &Primitive) -> Result<i64, RuntimeError> {
+    let n = expect_number(name, arg)?;
+    if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER {
+        Ok(n as i64)
+    } else {
+        Err(RuntimeError::InvalidArgument(format!(
+            "{}: expected integer, found {}",
+            name, n
+        )))
+    }
+}
+
+/// 引数が関数であることを検査する
+fn expect_function(name: &str, arg: &Primitive) -> Result<Rc<Function>, RuntimeError> {
+    match arg {
+        Primitive::Function(function) => Ok(Rc::clone(function)),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "{}: expected function, found {}",
+            name, arg
+        ))),
+    }
+}
+
+/// 引数が0以上の整数であることを検査する
+fn expect_non_negative_integer(name: &str, arg: &Primitive) -> Result<usize, RuntimeError> {
+    let n = expect_number(name, arg)?;
+    if n >= 0.0 && n.fract() == 0.0 && n <= i32::MAX as f64 {
+        Ok(n as usize)
+    } else {
+        Err(RuntimeError::InvalidArgument(format!(
+            "{}: expected non-negative integer, found {}",
+            name, n
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::tests::{eval, eval_error};
+
+    fn numbers(values: &[f64]) -> Primitive {
+        values.iter().map(|n| Primitive::Number(*n)).collect::<Vec<_>>().into()
+    }
+
+    #[test]
+    fn clamp_bounds_value() {
+        assert_eq!(eval("clamp(-5, 0, 10)"), Ok(Primitive::Number(0.0)));
+        assert_eq!(eval("clamp(5, 0, 10)"), Ok(Primitive::Number(5.0)));
+        assert_eq!(eval("clamp(15, 0, 10)"), Ok(Primitive::Number(10.0)));
+    }
+
+    #[test]
+    fn clamp_rejects_inverted_range() {
+        assert!(eval_error("clamp(5, 10, 0)").contains("low (10) must not be greater than high (0)"));
+        assert!(matches!(eval("clamp(\"a\", 0, 10)"), Err(RuntimeError::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn round_to_digits() {
+        assert_eq!(eval("round(3.14159, 2) == 3.14"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("round(3.7)"), Ok(Primitive::Number(4.0)));
+        assert!(eval_error("round(3.14, -1)").contains("expected non-negative integer"));
+    }
+
+    #[test]
+    fn round_with_too_many_digits_returns_input() {
+        assert_eq!(eval("round(1.25, 400)"), Ok(Primitive::Number(1.25)));
+        assert_eq!(eval("round(-1.25, 400)"), Ok(Primitive::Number(-1.25)));
+    }
+
+    #[test]
+    fn optional_arguments_report_range() {
+        assert_eq!(eval_error("round()"), "round expects 1 to 2 arguments, but 0 given");
+        assert_eq!(eval_error("sort([1], 2, 3)"), "sort expects 1 to 2 arguments, but 3 given");
+        assert_eq!(eval_error("min(1)"), "min expects 2 to 3 arguments, but 1 given");
+        assert_eq!(eval_error("max(1, 2, 3, 4)"), "max expects 2 to 3 arguments, but 4 given");
+    }
+
+    #[test]
+    fn env_reads_variable() {
+        env::set_var("SIMPLE_CALC_TEST_ENV", "value");
+        assert_eq!(eval("env(\"SIMPLE_CALC_TEST_ENV\") == \"value\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("env(\"SIMPLE_CALC_TEST_UNSET\") == \"\""), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn time_ms_is_monotonic() {
+        assert_eq!(eval("first = time_ms()\nsecond = time_ms()\nsecond >= first"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval_error("time_ms(1)"), "time_ms expects 0 argument(s), but 1 given");
+    }
+
+    #[test]
+    fn push_mutates_shared_array() {
+        assert_eq!(eval("a = [1]\nb = a\npush(a, 2)\nlen(b)"), Ok(Primitive::Number(2.0)));
+    }
+
+    #[test]
+    fn pop_on_empty_array_errors() {
+        assert_eq!(eval("pop([1, 2])"), Ok(Primitive::Number(2.0)));
+        assert_eq!(eval_error("pop([])"), "invalid argument: pop: array is empty");
+    }
+
+    #[test]
+    fn slice_copies_range() {
+        assert_eq!(eval("slice([1, 2, 3, 4], 1, 3)"), Ok(numbers(&[2.0, 3.0])));
+        assert!(eval_error("slice([1], 0, 2)").contains("out of bounds"));
+    }
+
+    #[test]
+    fn number_builtins() {
+        assert_eq!(eval("sign(-3)"), Ok(Primitive::Number(-1.0)));
+        assert_eq!(eval("abs(-3)"), Ok(Primitive::Number(3.0)));
+        assert_eq!(eval("gcd(12, 18)"), Ok(Primitive::Number(6.0)));
+        assert_eq!(eval("lcm(4, 6)"), Ok(Primitive::Number(12.0)));
+        assert_eq!(eval("lcm(-4, 6)"), Ok(Primitive::Number(12.0)));
+        assert_eq!(eval_error("gcd(1.5, 3)"), "invalid argument: gcd: expected integer, found 1.5");
+    }
+
+    #[test]
+    fn lcm_overflow_errors() {
+        assert_eq!(
+            eval_error("lcm(9007199254740991, 9007199254740990)"),
+            "integer overflow: lcm(9007199254740991, 9007199254740990)"
+        );
+        assert_eq!(eval("lcm(9007199254740991, 1)"), Ok(Primitive::Number(9007199254740991.0)));
+    }
+
+    #[test]
+    fn map_and_filter() {
+        assert_eq!(eval("map([1, 2, 3], fn(x) { x * 2 })"), Ok(numbers(&[2.0, 4.0, 6.0])));
+        assert_eq!(eval("filter([-1, 2, -3, 4], fn(x) { x > 0 })"), Ok(numbers(&[2.0, 4.0])));
+        assert_eq!(eval_error("map(1, fn(x) { x })"), "type mismatch: map: expected array, found 1");
+        assert_eq!(eval_error("map([1], 2)"), "type mismatch: map: expected function, found 2");
+        assert_eq!(
+            eval_error("filter([1], fn(x) { x })"),
+            "type mismatch: filter: callback must return boolean, found 1"
+        );
+    }
+
+    #[test]
+    fn reduce_folds_from_initial_value() {
+        assert_eq!(eval("reduce([1, 2, 3], fn(acc, x) { acc + x }, 0)"), Ok(Primitive::Number(6.0)));
+        assert_eq!(eval("reduce([], fn(acc, x) { acc + x }, 10)"), Ok(Primitive::Number(10.0)));
+        assert_eq!(eval("reduce([\"a\", \"b\"], fn(acc, x) { acc + x }, \"\") == \"ab\""), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn padding_aligns_values() {
+        assert_eq!(eval("pad_start(42, 5) == \"   42\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("pad_start(12345, 3) == \"12345\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("pad_end(\"ab\", 4) + \"|\" == \"ab  |\""), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn sleep_waits_at_least_duration() {
+        let start = std::time::Instant::now();
+        assert_eq!(eval("sleep(5)"), Ok(Primitive::Null));
+        assert!(start.elapsed() >= Duration::from_millis(5));
+        assert_eq!(eval_error("sleep(-1)"), "invalid argument: sleep: expected non-negative integer, found -1");
+    }
+
+    #[test]
+    fn nan_and_infinity() {
+        assert_eq!(eval("isnan(0 / 0)"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("isnan(1)"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("isinf(1 / 0)"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("isinf(-1 / 0)"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("isinf(0 / 0)"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("(0 / 0) == (0 / 0)"), Ok(Primitive::Boolean(false)));
+
+        let display = |code| eval(code).unwrap().to_string();
+        assert_eq!(display("0 / 0"), "NaN");
+        assert_eq!(display("1 / 0"), "Infinity");
+        assert_eq!(display("-1 / 0"), "-Infinity");
+    }
+
+    #[test]
+    fn repeat_strings_and_arrays() {
+        assert_eq!(eval("repeat(\"ab\", 3)"), Ok(Primitive::String(Rc::new("ababab".to_string()))));
+        assert_eq!(eval("repeat([0], 3) == [0, 0, 0]"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("repeat(\"ab\", 0)"), Ok(Primitive::String(Rc::new(String::new()))));
+        assert_eq!(eval("len(repeat([1], 0))"), Ok(Primitive::Number(0.0)));
+        assert!(eval_error("repeat(\"ab\", -1)").contains("non-negative integer"));
+        assert!(eval("repeat(\"ab\", 1.5)").is_err());
+    }
+
+    #[test]
+    fn index_of_and_contains() {
+        assert_eq!(eval("index_of([1, \"a\", 3], \"a\")"), Ok(Primitive::Number(1.0)));
+        assert_eq!(eval("index_of([1, 2, 3], 4)"), Ok(Primitive::Number(-1.0)));
+        assert_eq!(eval("index_of([], 1)"), Ok(Primitive::Number(-1.0)));
+        assert_eq!(eval("contains([1, 2, 3], 3)"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("contains([1, 2, 3], 4)"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("contains([], 1)"), Ok(Primitive::Boolean(false)));
+        assert!(eval("contains(1, 1)").is_err());
+    }
+
+    #[test]
+    fn sort_arrays() {
+        assert_eq!(eval("sort([3, 1, 2]) == [1, 2, 3]"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("sort([\"b\", \"c\", \"a\"]) == [\"a\", \"b\", \"c\"]"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("sort([1, 3, 2], fn(a, b) { return b - a }) == [3, 2, 1]"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("a = [2, 1]\nsort(a)\na == [2, 1]"), Ok(Primitive::Boolean(true)));
+        assert!(eval("sort([1, \"a\"])").is_err());
+    }
+
+    #[test]
+    fn reverse_strings_and_arrays() {
+        assert_eq!(eval("reverse(\"héllo\") == \"olléh\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("reverse([1, 2, 3]) == [3, 2, 1]"), Ok(Primitive::Boolean(true)));
+        assert!(eval("reverse(1)").is_err());
+    }
+
+    #[test]
+    fn help_lists_builtins_and_functions() {
+        let help = eval("fn add(a, b) { a + b }\nhelp()").unwrap().to_string();
+        assert!(help.contains("abs"), "{}", help);
+        assert!(help.contains("add(a, b)"), "{}", help);
+    }
+
+    #[test]
+    fn character_builtins() {
+        assert_eq!(eval("ord(\"A\") == 65"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("ord('é')"), Ok(Primitive::Number(233.0)));
+        assert_eq!(eval("chr(65) == 'A'"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("chr(65) == \"A\""), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("char_at(\"héllo\", 1) == 'é'"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("char_at(\"héllo\", 1) == 'e'"), Ok(Primitive::Boolean(false)));
+        assert!(eval_error("chr(1114112)").contains("invalid code point"));
+        assert!(eval_error("chr(55296)").contains("invalid code point"));
+        assert!(eval("ord(\"ab\")").is_err());
+    }
+
+    #[test]
+    fn chars_differ_from_strings_in_searches() {
+        assert_eq!(eval("index_of(['a', 'b'], chr(98))"), Ok(Primitive::Number(1.0)));
+        assert_eq!(eval("contains([\"a\"], 'a')"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("count(['a', \"a\", 'a'], 'a')"), Ok(Primitive::Number(2.0)));
+        assert_eq!(eval("[chr(65), 1] == ['A', 1]"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("'A' in [\"A\"]"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("'A' in \"ABC\""), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn method_calls_dispatch_to_builtins() {
+        assert_eq!(eval("\"abc\".len() == 3"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("a = [1, 2, 3]\na.push(4)\na == [1, 2, 3, 4]"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval_error("\"abc\".nope()"), "string has no method nope");
+    }
+
+    #[test]
+    fn deep_equal_compares_structure() {
+        assert_eq!(eval("a = [1, [2]]\nb = [1, [2]]\ndeep_equal(a, b)"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("a = [1, [2]]\nb = [1, [2]]\na === b"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("deep_equal({ x: [1, { y: 2 }] }, { x: [1, { y: 2 }] })"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("deep_equal({ x: [1, { y: 2 }] }, { x: [1, { y: 3 }] })"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("deep_equal([1, 2], [1, 2, 3])"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("a = [1]\npush(a, a)\nb = [1]\npush(b, b)\ndeep_equal(a, b)"), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn equality_handles_cyclic_values() {
+        assert_eq!(eval("a = [1]\npush(a, a)\nb = [1]\npush(b, b)\na == b"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("a = [1]\npush(a, a)\nb = [2]\npush(b, b)\na != b"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("a = {}\na.self = a\nb = {}\nb.self = b\na == b"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("a = [1]\npush(a, a)\na in [a]"), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn min_and_max_compare_numbers_and_strings() {
+        assert_eq!(eval("max(\"apple\", \"banana\") == \"banana\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("min(3, 7) == 3"), Ok(Primitive::Boolean(true)));
+        assert!(eval("max(1, \"a\")").is_err());
+        assert_eq!(
+            eval("max(\"ccc\", \"a\", fn(a, b) { return len(b) - len(a) }) == \"a\""),
+            Ok(Primitive::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn to_fixed_formats_digits() {
+        assert_eq!(eval("to_fixed(3.1, 2) == \"3.10\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("to_fixed(2, 0) == \"2\""), Ok(Primitive::Boolean(true)));
+        assert!(eval("to_fixed(2, -1)").is_err());
+    }
+
+    #[test]
+    fn count_strings_and_arrays() {
+        assert_eq!(eval("count(\"banana\", \"a\") == 3"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("count(\"aaaa\", \"aa\")"), Ok(Primitive::Number(2.0)));
+        assert_eq!(eval("count([1, 2, 1, 3, 1], 1) == 3"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("count(\"banana\", \"x\")"), Ok(Primitive::Number(0.0)));
+        assert_eq!(eval("count([], 1)"), Ok(Primitive::Number(0.0)));
+        assert_eq!(eval_error("count(5, 1)"), "type mismatch: count: expected string or array, found 5");
+    }
+
+    #[test]
+    fn rounding_family() {
+        assert_eq!(eval("trunc(-1.7) == -1"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("floor(-1.7) == -2"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("ceil(-1.7) == -1"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("fract(2.25) == 0.25"), Ok(Primitive::Boolean(true)));
+        assert!(eval("trunc(\"1\")").is_err());
+        assert!(eval("fract(null)").is_err());
+    }
+
+    #[test]
+    fn to_base_renders_integers() {
+        assert_eq!(eval("to_base(255, 16) == \"ff\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("to_base(5, 2) == \"101\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("to_base(-35, 36) == \"-z\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("to_base(0, 8) == \"0\""), Ok(Primitive::Boolean(true)));
+        assert!(eval("to_base(5, 1)").is_err());
+        assert!(eval("to_base(5, 37)").is_err());
+        assert!(eval("to_base(1.5, 2)").is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::fmt::Display;
+use std::rc::Rc;
+
+use crate::interpreter::compound_operator;
+use crate::parse::{Expr, Statement};
+use crate::token::Operator;
+use crate::types::Primitive;
+
+/// バイトコードの命令
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    /// 定数を積む
+    Constant(Primitive),
+    /// 変数の値を積む (未定義なら0)
+    Load(String),
+    /// 先頭の値を取り出さずに変数に代入する
+    Store(String),
+    /// 先頭の値を捨てる
+    Pop,
+    /// 先頭の値に前置演算子を適用する
+    Prefix(Operator),
+    /// 先頭の2つの値に中置演算子を適用する
+    Infix(Operator),
+    /// 無条件に移動する
+    Jump(usize),
+    /// 先頭の真偽値を取り出し，falseなら移動する
+    JumpIfFalse(usize),
+    /// 先頭の値だけで演算子の結果が決まるなら，値を残したまま移動する
+    ShortCircuit(Operator, usize),
+    /// 先頭のn個の値を取り出し，空白で区切って表示する
+    Print(usize),
+    /// ブロックのスコープに入る
+    EnterScope,
+    /// ブロックのスコープを出る
+    ExitScope,
+    /// ループの繰り返し回数を0に戻す
+    ResetIterations(usize),
+    /// ループの繰り返し回数を数える
+    CountIteration(usize),
+}
+
+/// コンパイルのエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    /// バイトコードに対応していない構文
+    Unsupported(String),
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Unsupported(construct) => write!(f, "{} is not supported by the bytecode compiler", construct),
+        }
+    }
+}
+
+/// コンパイルしたプログラム
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    /// 繰り返し回数を数えるループの数
+    pub loops: usize,
+}
+
+/// 文の列をバイトコードにコンパイルする
+/// 現在は算術演算，変数，条件演算子，if文，while文に対応する
+pub fn compile(statements: &[Statement]) -> Result<Program, CompileError> {
+    let mut compiler = Compiler {
+        instructions: Vec::new(),
+        loops: Vec::new(),
+        loop_count: 0,
+        depth: 0,
+    };
+    compiler.compile_statements(statements)?;
+
+    Ok(Program {
+        instructions: compiler.instructions,
+        loops: compiler.loop_count,
+    })
+}
+
+/// コンパイル中のループ
+struct Loop {
+    /// continueで移動する位置
+    start: usize,
+    /// ループに入ったときのスコープの深さ
+    depth: usize,
+    /// ループの後に移動先を書き換えるbreakのJump命令の位置
+    breaks: Vec<usize>,
+}
+
+struct Compiler {
+    instructions: Vec<Instruction>,
+    /// コンパイル中のループ(内側ほど後ろ)
+    loops: Vec<Loop>,
+    /// これまでにコンパイルしたループの数
+    loop_count: usize,
+    /// 現在のスコープの深さ
+    depth: usize,
+}
+
+impl Compiler {
+    fn compile_statements(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        statements.iter().try_for_each(|statement| self.compile_statement(statement))
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        match statement {
+            Statement::Expr(expr) => {
+                self.compile_expr(expr)?;
+                self.emit(Instruction::Pop);
+            }
+            Statement::Print(arguments) => {
+                for argument in arguments {
+                    self.compile_expr(argument)?;
+                }
+                self.emit(Instruction::Print(arguments.len()));
+            }
+            Statement::Block(statements) => {
+                self.emit(Instruction::EnterScope);
+                self.depth += 1;
+                self.compile_statements(statements)?;
+                self.depth -= 1;
+                self.emit(Instruction::ExitScope);
+            }
+            Statement::If { condition, block, else_block } => {
+                self.compile_expr(condition)?;
+                let jump_to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.compile_statement(block)?;
+
+                match else_block {
+                    Some(else_block) => {
+                        let jump_to_end = self.emit(Instruction::Jump(0));
+                        self.patch(jump_to_else);
+                        self.compile_statement(else_block)?;
+                        self.patch(jump_to_end);
+                    }
+                    None => self.patch(jump_to_else),
+                }
+            }
+            Statement::While { condition, block, else_block } => {
+                self.compile_while(condition, block, else_block.as_deref())?;
+            }
+            Statement::Break(value) => {
+                // whileではbreakの値は使われないため，評価だけして捨てる
+                if let Some(value) = value {
+                    self.compile_expr(value)?;
+                    self.emit(Instruction::Pop);
+                }
+                let Some(depth) = self.loops.last().map(|current| current.depth) else {
+                    return Err(CompileError::Unsupported("break outside of loop".to_string()));
+                };
+                self.exit_scopes(depth);
+                let jump = self.emit(Instruction::Jump(0));
+                self.loops.last_mut().unwrap().breaks.push(jump);
+            }
+            Statement::Continue => {
+                let Some((start, depth)) = self.loops.last().map(|current| (current.start, current.depth)) else {
+                    return Err(CompileError::Unsupported("continue outside of loop".to_string()));
+                };
+                self.exit_scopes(depth);
+                self.emit(Instruction::Jump(start));
+            }
+            Statement::Eprint(_) => return Err(CompileError::Unsupported("eprint".to_string())),
+            Statement::WhileLet { .. } => return Err(CompileError::Unsupported("while let".to_string())),
+            Statement::Return(_) => return Err(CompileError::Unsupported("return".to_string())),
+            Statement::Loop(_) => return Err(CompileError::Unsupported("loop".to_string())),
+            Statement::Function { .. } => return Err(CompileError::Unsupported("function".to_string())),
+            Statement::Include(_) => return Err(CompileError::Unsupported("include".to_string())),
+            Statement::TryCatch { .. } => return Err(CompileError::Unsupported("try".to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn compile_while(&mut self, condition: &Expr, block: &Statement, else_block: Option<&Statement>) -> Result<(), CompileError> {
+        let counter = self.loop_count;
+        self.loop_count += 1;
+        self.emit(Instruction::ResetIterations(counter));
+
+        let start = self.instructions.len();
+        self.compile_expr(condition)?;
+        let jump_to_else = self.emit(Instruction::JumpIfFalse(0));
+        self.emit(Instruction::CountIteration(counter));
+
+        self.loops.push(Loop { start, depth: self.depth, breaks: Vec::new() });
+        self.compile_statement(block)?;
+        let current = self.loops.pop().unwrap();
+        self.emit(Instruction::Jump(start));
+
+        // 条件が偽になって終わったときだけelseを実行する
+        self.patch(jump_to_else);
+        if let Some(else_block) = else_block {
+            self.compile_statement(else_block)?;
+        }
+        for jump in current.breaks {
+            self.patch(jump);
+        }
+
+        Ok(())
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        match expr {
+            Expr::Identifier(name) => {
+                self.emit(Instruction::Load(name.clone()));
+            }
+            Expr::Number(n) => {
+                self.emit(Instruction::Constant(Primitive::Number(*n)));
+            }
+            Expr::String(s) => {
+                self.emit(Instruction::Constant(Primitive::String(Rc::clone(&s.value))));
+            }
+            Expr::Char(c) => {
+                self.emit(Instruction::Constant(Primitive::Char(*c)));
+            }
+            Expr::Null => {
+                self.emit(Instruction::Constant(Primitive::Null));
+            }
+            Expr::Boolean(b) => {
+                self.emit(Instruction::Constant(Primitive::Boolean(*b)));
+            }
+            Expr::PrefixExpr { operator, right } => {
+                self.compile_expr(right)?;
+                self.emit(Instruction::Prefix(operator.clone()));
+            }
+            Expr::InfixExpr { left, operator, right } => self.compile_infix_expr(left, operator, right)?,
+            Expr::Conditional { condition, then_value, else_value } => {
+                self.compile_expr(condition)?;
+                let jump_to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.compile_expr(then_value)?;
+                let jump_to_end = self.emit(Instruction::Jump(0));
+                self.patch(jump_to_else);
+                self.compile_expr(else_value)?;
+                self.patch(jump_to_end);
+            }
+            Expr::Local(_) => return Err(CompileError::Unsupported("local variable".to_string())),
+            Expr::PostfixExpr { .. } => return Err(CompileError::Unsupported("postfix operator".to_string())),
+            Expr::Typeof(_) => return Err(CompileError::Unsupported("typeof".to_string())),
+            Expr::Array(_) => return Err(CompileError::Unsupported("array".to_string())),
+            Expr::Tuple(_) => return Err(CompileError::Unsupported("tuple".to_string())),
+            Expr::Loop(_) => return Err(CompileError::Unsupported("loop".to_string())),
+            Expr::Function(_) => return Err(CompileError::Unsupported("function".to_string())),
+            Expr::Call { .. } => return Err(CompileError::Unsupported("function call".to_string())),
+            Expr::Index { .. } => return Err(CompileError::Unsupported("index".to_string())),
+            Expr::Object(_) => return Err(CompileError::Unsupported("object".to_string())),
+            Expr::Field { .. } => return Err(CompileError::Unsupported("field access".to_string())),
+            Expr::Spread(_) => return Err(CompileError::Unsupported("spread".to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn compile_infix_expr(&mut self, left: &Expr, operator: &Operator, right: &Expr) -> Result<(), CompileError> {
+        let target = match left {
+            Expr::Identifier(name) => Some(name),
+            _ => None,
+        };
+
+        match operator {
+            Operator::LogicalAnd | Operator::LogicalOr | Operator::Coalesce => {
+                self.compile_expr(left)?;
+                let jump = self.emit(Instruction::ShortCircuit(operator.clone(), 0));
+                self.emit(Instruction::Pop);
+                self.compile_expr(right)?;
+                self.patch(jump);
+            }
+            Operator::AndAssign | Operator::OrAssign => {
+                let target = target.ok_or_else(unsupported_target)?;
+                self.compile_expr(left)?;
+                let jump = self.emit(Instruction::ShortCircuit(operator.clone(), 0));
+                self.emit(Instruction::Pop);
+                self.compile_expr(right)?;
+                self.emit(Instruction::Store(target.clone()));
+                self.patch(jump);
+            }
+            Operator::Assign => {
+                let target = target.ok_or_else(unsupported_target)?;
+                self.compile_expr(right)?;
+                self.emit(Instruction::Store(target.clone()));
+            }
+            _ => match compound_operator(operator) {
+                Some(operator) => {
+                    let target = target.ok_or_else(unsupported_target)?;
+                    self.compile_expr(left)?;
+                    self.compile_expr(right)?;
+                    self.emit(Instruction::Infix(operator));
+                    self.emit(Instruction::Store(target.clone()));
+                }
+                None => {
+                    self.compile_expr(left)?;
+                    self.compile_expr(right)?;
+                    self.emit(Instruction::Infix(operator.clone()));
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// break，continueで抜けるスコープを閉じる命令を出力する
+    fn exit_scopes(&mut self, depth: usize) {
+        for _ in depth..self.depth {
+            self.emit(Instruction::ExitScope);
+        }
+    }
+
+    /// 命令を追加し，その位置を返す
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.instructions.push(instruction);
+        self.instructions.len() - 1
+    }
+
+    /// 移動命令の移動先を次に追加する命令の位置に書き換える
+    fn patch(&mut self, position: usize) {
+        let target = self.instructions.len();
+        match &mut self.instructions[position] {
+            Instruction::Jump(to) | Instruction::JumpIfFalse(to) | Instruction::ShortCircuit(_, to) => *to = target,
+            instruction => panic!("cannot patch {:?}", instruction),
+        }
+    }
+}
+
+fn unsupported_target() -> CompileError {
+    CompileError::Unsupported("destructuring assignment".to_string())
+}
@@ -0,0 +1,96 @@
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::parse::{ParseError, Parser, Statement};
+use crate::token::Lexer;
+
+/// include文の展開のエラー
+#[derive(Debug)]
+pub enum IncludeError {
+    /// ファイルを読み込めない
+    Read {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// インクルードしたファイルの構文エラー
+    Parse {
+        path: PathBuf,
+        error: ParseError,
+    },
+    /// インクルードが循環している
+    Cycle {
+        path: PathBuf,
+    },
+}
+
+impl Display for IncludeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncludeError::Read { path, error } => write!(f, "{}: {}", path.display(), error),
+            IncludeError::Parse { path, error } => write!(f, "{}:{}", path.display(), error),
+            IncludeError::Cycle { path } => write!(f, "{}: include cycle detected", path.display()),
+        }
+    }
+}
+
+/// include文をインクルードしたファイルの文で置き換える
+/// 相対パスはインクルードする側のファイルのディレクトリから解決する
+/// file_pathはstatementsを読み込んだファイル (REPLではNone)
+pub fn expand(statements: Vec<Statement>, file_path: Option<&Path>) -> Result<Vec<Statement>, IncludeError> {
+    let mut expander = Expander { including: Vec::new() };
+    if let Some(canonical) = file_path.and_then(|path| path.canonicalize().ok()) {
+        expander.including.push(canonical);
+    }
+
+    let directory = file_path.and_then(Path::parent).unwrap_or(Path::new(""));
+    expander.expand(statements, directory)
+}
+
+struct Expander {
+    /// 展開中のファイル (インクルードの循環の検出に使う)
+    including: Vec<PathBuf>,
+}
+
+impl Expander {
+    fn expand(&mut self, statements: Vec<Statement>, directory: &Path) -> Result<Vec<Statement>, IncludeError> {
+        let mut expanded = Vec::new();
+        for statement in statements {
+            match statement {
+                Statement::Include(path) => expanded.extend(self.include(&directory.join(path))?),
+                statement => expanded.push(statement),
+            }
+        }
+
+        Ok(expanded)
+    }
+
+    /// ファイルを読み込み，その中のinclude文も展開する
+    fn include(&mut self, path: &Path) -> Result<Vec<Statement>, IncludeError> {
+        let read_error = |error| IncludeError::Read {
+            path: path.to_path_buf(),
+            error,
+        };
+
+        let canonical = path.canonicalize().map_err(read_error)?;
+        if self.including.contains(&canonical) {
+            return Err(IncludeError::Cycle {
+                path: path.to_path_buf(),
+            });
+        }
+
+        let code = fs::read_to_string(path).map_err(read_error)?;
+        let mut parser = Parser::new(Lexer::new(&code));
+        let statements = parser.parse().map_err(|error| IncludeError::Parse {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        self.including.push(canonical);
+        let expanded = self.expand(statements, path.parent().unwrap_or(Path::new("")));
+        self.including.pop();
+
+        expanded
+    }
+}
@@ -1,21 +1,227 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
 use std::process::exit;
 use std::rc::Rc;
 
-use crate::parse::{Expr, Statement};
+use crate::builtins;
+use crate::parse::{Expr, Function, Statement};
 use crate::token::Operator;
-use crate::types::{Primitive, LogicalAnd, LogicalOr};
+use crate::types::{Primitive, TypeName};
 
-struct Context {
+/// 実行時エラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// 未定義の関数
+    UndefinedFunction(String),
+    /// 引数の数が合わない
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+    /// 引数の数が範囲外 (省略できる引数を持つ関数)
+    ArgumentCountRange {
+        name: String,
+        min: usize,
+        max: usize,
+        actual: usize,
+    },
+    /// 型が合わない
+    TypeMismatch(String),
+    /// 引数の値が不正
+    InvalidArgument(String),
+    /// 添字が範囲外
+    IndexOutOfRange {
+        index: f64,
+        length: usize,
+    },
+    /// ループの外でのbreak，continue
+    OutsideLoop(String),
+    /// 式としてのloopの中でのreturn
+    ReturnInExpression,
+    /// ループの繰り返し回数が上限を超えた
+    IterationLimit(usize),
+    /// 分割代入の変数と値の数が合わない
+    TupleArity {
+        expected: usize,
+        actual: usize,
+    },
+    /// スクリプトが発生させたエラー
+    UserError(String),
+    /// 整数の演算で範囲を超えた (Overflow::Checkedのとき)
+    IntegerOverflow(String),
+    /// 型にないメソッドの呼び出し
+    UndefinedMethod {
+        type_name: &'static str,
+        name: String,
+    },
+    /// トップレベルのreturnの値が終了コードとして不正
+    InvalidExitCode(String),
+    /// 代入の左辺が変数，フィールド，それらのタプルのどれでもない
+    InvalidAssignmentTarget,
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::UndefinedFunction(name) => write!(f, "undefined function: {}", name),
+            RuntimeError::ArgumentCount { name, expected, actual } => write!(
+                f,
+                "{} expects {} argument(s), but {} given",
+                name, expected, actual
+            ),
+            RuntimeError::ArgumentCountRange { name, min, max, actual } => write!(
+                f,
+                "{} expects {} to {} arguments, but {} given",
+                name, min, max, actual
+            ),
+            RuntimeError::TypeMismatch(message) => write!(f, "type mismatch: {}", message),
+            RuntimeError::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            RuntimeError::IndexOutOfRange { index, length } => {
+                write!(f, "index {} out of range for length {}", index, length)
+            }
+            RuntimeError::OutsideLoop(keyword) => write!(f, "{} outside of loop", keyword),
+            RuntimeError::ReturnInExpression => write!(f, "return inside loop expression"),
+            RuntimeError::IterationLimit(limit) => {
+                write!(f, "loop exceeded the iteration limit of {}", limit)
+            }
+            RuntimeError::TupleArity { expected, actual } => write!(
+                f,
+                "cannot destructure {} value(s) into {} variable(s)",
+                actual, expected
+            ),
+            RuntimeError::UserError(message) => write!(f, "error: {}", message),
+            RuntimeError::IntegerOverflow(expression) => write!(f, "integer overflow: {}", expression),
+            RuntimeError::UndefinedMethod { type_name, name } => {
+                write!(f, "{} has no method {}", type_name, name)
+            }
+            RuntimeError::InvalidExitCode(value) => {
+                write!(f, "exit code must be an integer from 0 to 255, found {}", value)
+            }
+            RuntimeError::InvalidAssignmentTarget => write!(f, "invalid left hand side of assignment"),
+        }
+    }
+}
+
+/// ビット演算，シフト演算で値が32ビット整数の範囲を超えたときの振る舞い
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Overflow {
+    /// 2の補数で折り返す (シフト量は下位5ビットだけを使う)
+    #[default]
+    Wrapping,
+    /// 表せる最大値，最小値にする
+    Saturating,
+    /// 実行時エラーにする
+    Checked,
+}
+
+impl Overflow {
+    /// 数値を32ビット整数に変換する (小数部は切り捨てる)
+    fn to_i32(self, n: f64, expression: impl FnOnce() -> String) -> Result<i32, RuntimeError> {
+        let n = n.trunc();
+        match self {
+            Overflow::Wrapping => Ok(n as i64 as i32),
+            Overflow::Saturating => Ok(n as i32),
+            Overflow::Checked if (i32::MIN as f64..=i32::MAX as f64).contains(&n) => Ok(n as i32),
+            Overflow::Checked => Err(RuntimeError::IntegerOverflow(expression())),
+        }
+    }
+}
+
+/// インタプリタに登録された関数
+type NativeFunction = Rc<dyn Fn(&[Primitive]) -> Result<Primitive, RuntimeError>>;
+
+/// 実行をトレースする関数
+type Tracer = Box<dyn FnMut(TraceEvent)>;
+
+/// トレースで通知する出来事
+#[derive(Debug)]
+pub enum TraceEvent<'a> {
+    /// 文を実行する直前
+    Statement(&'a Statement),
+    /// 式を評価した直後 (評価した値を持つ)
+    Eval(&'a Expr, &'a Primitive),
+}
+
+/// 文を実行した後の制御フロー
+#[derive(Debug, Clone, PartialEq)]
+enum ControlFlow {
+    /// 次の文に進む
+    Next,
+    /// ループを抜ける (値を持つことができる)
+    Break(Option<Primitive>),
+    /// ループの次の繰り返しに進む
+    Continue,
+    /// 関数から値を返す
+    Return(Primitive),
+}
+
+/// 変数の環境
+pub struct Context {
     pub vars: HashMap<String, Primitive>,
+    /// 実行中のブロックのスコープ(内側ほど後ろ)
+    pub blocks: Vec<HashMap<String, Primitive>>,
+    /// 仮引数の名前 (i番目の仮引数の値はslots[i])
+    parameters: Vec<String>,
+    /// 仮引数の値 (解決済みの変数は添字で読み書きする)
+    pub slots: Vec<Primitive>,
 }
 
 impl Context {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Context {
             vars: HashMap::new(),
+            blocks: Vec::new(),
+            parameters: Vec::new(),
+            slots: Vec::new(),
         }
     }
+
+    /// 仮引数に実引数を束縛した関数の環境をつくる
+    fn with_arguments(parameters: &[String], arguments: Vec<Primitive>) -> Self {
+        Context {
+            parameters: parameters.to_vec(),
+            slots: arguments,
+            ..Context::new()
+        }
+    }
+
+    /// 内側のスコープから順に変数を探す
+    pub fn get(&self, name: &str) -> Option<&Primitive> {
+        self.blocks
+            .iter()
+            .rev()
+            .find_map(|block| block.get(name))
+            .or_else(|| self.vars.get(name))
+            .or_else(|| self.parameter_index(name).map(|index| &self.slots[index]))
+    }
+
+    fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.parameters.iter().position(|parameter| parameter == name)
+    }
+
+    /// 変数に代入する
+    /// 見えるスコープに変数がなければ，最も内側のスコープに定義する
+    pub fn assign(&mut self, name: &str, value: Primitive) {
+        let slot = self
+            .blocks
+            .iter_mut()
+            .rev()
+            .find_map(|block| block.get_mut(name))
+            .or_else(|| self.vars.get_mut(name))
+            .or_else(|| {
+                let index = self.parameters.iter().position(|parameter| parameter == name)?;
+                Some(&mut self.slots[index])
+            });
+        if let Some(slot) = slot {
+            *slot = value;
+            return;
+        }
+
+        let scope = self.blocks.last_mut().unwrap_or(&mut self.vars);
+        scope.insert(name.to_string(), value);
+    }
 }
 
 
@@ -23,64 +229,343 @@ pub struct Interpreter {
     global_context: Context,
 
     // 関数の呼び出し時にスタックに積む
-    //TODO: 関数実装
     stack: Vec<Context>,
+
+    /// 名前で呼び出せるRustの関数
+    builtins: HashMap<String, NativeFunction>,
+
+    /// 実行をトレースする関数 (Noneならトレースしない)
+    tracer: Option<Tracer>,
+
+    /// 1つのループで繰り返せる回数の上限 (Noneなら無制限)
+    iteration_limit: Option<usize>,
+
+    /// 整数の演算で範囲を超えたときの振る舞い
+    overflow: Overflow,
+
+    /// print文で数値を表示するときの小数点以下の桁数 (Noneなら最短の表現)
+    precision: Option<usize>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        Interpreter {
+        let mut interpreter = Interpreter {
             global_context: Context::new(),
             stack: Vec::new(),
+            builtins: HashMap::new(),
+            tracer: None,
+            iteration_limit: None,
+            overflow: Overflow::default(),
+            precision: None,
+        };
+
+        for (name, builtin) in builtins::BUILTINS {
+            interpreter.register_builtin(name, *builtin);
         }
+
+        interpreter
+    }
+
+    /// スクリプトから呼び出せる関数を登録する
+    /// 同じ名前の関数がすでにあれば置き換える
+    pub fn register_builtin(
+        &mut self,
+        name: &str,
+        function: impl Fn(&[Primitive]) -> Result<Primitive, RuntimeError> + 'static,
+    ) {
+        self.builtins.insert(name.to_string(), Rc::new(function));
+    }
+
+    /// 文の実行と式の評価のたびに呼び出される関数を設定する
+    pub fn set_tracer(&mut self, tracer: impl FnMut(TraceEvent) + 'static) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// 1つのループで繰り返せる回数の上限を設定する
+    pub fn set_iteration_limit(&mut self, limit: Option<usize>) {
+        self.iteration_limit = limit;
     }
 
-    // TODO: こっちをrunにする
-    fn run_block(&mut self, statements: Statement) {
+    /// ビット演算，シフト演算で値が範囲を超えたときの振る舞いを設定する
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    /// print文で数値を表示するときの小数点以下の桁数を設定する
+    pub fn set_precision(&mut self, precision: Option<usize>) {
+        self.precision = precision;
+    }
+
+    /// print文で表示する文字列に値を変換する
+    pub fn render(&self, value: &Primitive) -> String {
+        render(value, self.precision)
+    }
+
+    /// 呼び出せる組み込み関数の名前を名前順に返す
+    pub fn builtin_names(&self) -> Vec<&str> {
+        let mut names = self.builtins
+            .keys()
+            .map(String::as_str)
+            .chain(builtins::HIGHER_ORDER_BUILTINS.iter().map(|(name, _)| *name))
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+
+        names
+    }
+
+    /// グローバル変数を名前順に返す
+    pub fn globals(&self) -> Vec<(&str, &Primitive)> {
+        let mut globals = self.global_context.vars
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+            .collect::<Vec<_>>();
+        globals.sort_by_key(|(name, _)| *name);
+
+        globals
+    }
+
+    /// グローバル変数の値を返す
+    pub fn global(&self, name: &str) -> Option<&Primitive> {
+        self.global_context.vars.get(name)
+    }
+
+    /// ブロックを新しいスコープで実行する
+    /// ブロック内で定義された変数はブロックを抜けると破棄される
+    fn run_block(&mut self, statements: &Statement) -> Result<ControlFlow, RuntimeError> {
         let Statement::Block(statements) = statements else {
             panic!("invalid type")
         };
 
-        self.run(&statements);
+        self.context_mut().blocks.push(HashMap::new());
+        let flow = self.execute(statements);
+        self.context_mut().blocks.pop();
+
+        flow
+    }
+
+    /// try文を実行する
+    /// 実行時エラーが発生した場合は，そのメッセージをnameに束縛してcatch_blockを実行する
+    fn execute_try(&mut self, try_block: &Statement, name: &str, catch_block: &Statement) -> Result<ControlFlow, RuntimeError> {
+        let error = match self.run_block(try_block) {
+            Ok(flow) => return Ok(flow),
+            Err(error) => error,
+        };
+
+        let mut scope = HashMap::new();
+        scope.insert(name.to_string(), Primitive::String(Rc::new(error.to_string())));
+
+        self.context_mut().blocks.push(scope);
+        let flow = self.run_block(catch_block);
+        self.context_mut().blocks.pop();
+
+        flow
+    }
+
+    /// 文の列を実行する
+    /// 最後の文が式文であればその値を，そうでなければnullを返す
+    pub fn run(&mut self, statements: &[Statement]) -> Result<Primitive, RuntimeError> {
+        let mut values = self.run_returning(statements)?;
+        match statements.last() {
+            Some(Statement::Expr(_)) => Ok(values.pop().unwrap_or(Primitive::Null)),
+            _ => Ok(Primitive::Null),
+        }
+    }
+
+    /// 文の列を実行し，トップレベルの式文の値をすべて返す
+    /// 実行時エラーが発生した時点で実行を止め，そのエラーを返す
+    pub fn run_returning(&mut self, statements: &[Statement]) -> Result<Vec<Primitive>, RuntimeError> {
+        let mut values = Vec::new();
+        for statement in statements {
+            if let Statement::Expr(expr) = statement {
+                if let Some(tracer) = &mut self.tracer {
+                    tracer(TraceEvent::Statement(statement));
+                }
+                values.push(self.eval(expr)?);
+                continue;
+            }
+
+            match self.execute(std::slice::from_ref(statement))? {
+                ControlFlow::Next => (),
+                ControlFlow::Break(_) => return Err(RuntimeError::OutsideLoop("break".to_string())),
+                ControlFlow::Continue => return Err(RuntimeError::OutsideLoop("continue".to_string())),
+                ControlFlow::Return(code) => exit(exit_code(&code)?),
+            }
+        }
+
+        Ok(values)
     }
 
-    pub fn run(&mut self, statements: &[Statement]) {
+    /// 文の列を実行し，制御フローを返す
+    fn execute(&mut self, statements: &[Statement]) -> Result<ControlFlow, RuntimeError> {
         for statement in statements {
-            match statement {
+            if let Some(tracer) = &mut self.tracer {
+                tracer(TraceEvent::Statement(statement));
+            }
+
+            let flow = match statement {
                 Statement::Expr(expr) => {
-                    self.eval(expr);
+                    self.eval(expr)?;
+                    ControlFlow::Next
                 }
-                Statement::Print(expr) => {
-                    println!("{}", self.eval(expr));
+                Statement::Print(arguments) => {
+                    let values = self.eval_list(arguments)?;
+                    let values = values.iter().map(|value| self.render(value)).collect::<Vec<_>>();
+                    println!("{}", values.join(" "));
+                    ControlFlow::Next
                 }
-                Statement::Return(expr) => {
-                    let code = self.eval(expr);
-                    exit(code.into());
+                Statement::Eprint(arguments) => {
+                    let values = self.eval_list(arguments)?;
+                    let values = values.iter().map(|value| self.render(value)).collect::<Vec<_>>();
+                    eprintln!("{}", values.join(" "));
+                    ControlFlow::Next
                 }
-                Statement::Block(statements) => self.run(statements),
+                Statement::Return(expr) => ControlFlow::Return(self.eval(expr)?),
+                Statement::Block(_) => self.run_block(statement)?,
 
                 Statement::If { condition, block, else_block } => {
-                    let condition = self.eval(condition);
-                    let Primitive::Boolean(condition) = condition else {
-                        panic!("invalid type")
-                    };
-
-                    if condition {
-                        self.run_block(*block.to_owned());
+                    if self.eval_condition(condition)? {
+                        self.run_block(block)?
                     } else if let Some(else_block) = else_block {
-                        self.run_block(*else_block.to_owned());
+                        self.run_block(else_block)?
+                    } else {
+                        ControlFlow::Next
                     }
                 }
-                
+
+                Statement::While { condition, block, else_block } => {
+                    self.execute_while(condition, block, else_block.as_deref())?
+                }
+                Statement::WhileLet { name, value, block } => self.execute_while_let(name, value, block)?,
+                Statement::Break(value) => {
+                    let value = value.as_deref().map(|value| self.eval(value)).transpose()?;
+                    ControlFlow::Break(value)
+                }
+                Statement::Loop(block) => match self.execute_loop(block)? {
+                    ControlFlow::Break(_) => ControlFlow::Next,
+                    flow => flow,
+                },
+                Statement::Continue => ControlFlow::Continue,
+                Statement::Include(path) => panic!("include \"{}\" must be expanded before execution", path),
+                Statement::Function { name, function } => {
+                    self.set_variable(name, Primitive::Function(Rc::clone(function)));
+                    ControlFlow::Next
+                }
+                Statement::TryCatch { try_block, name, catch_block } => {
+                    self.execute_try(try_block, name, catch_block)?
+                }
+            };
+
+            if flow != ControlFlow::Next {
+                return Ok(flow);
+            }
+        }
+
+        Ok(ControlFlow::Next)
+    }
+
+    /// while文を実行する
+    /// breakせずにループを抜けた場合はelseブロックを実行する
+    fn execute_while(&mut self, condition: &Expr, block: &Statement, else_block: Option<&Statement>) -> Result<ControlFlow, RuntimeError> {
+        let mut iterations = 0;
+        while self.eval_condition(condition)? {
+            self.count_iteration(&mut iterations)?;
+            match self.run_block(block)? {
+                ControlFlow::Break(_) => return Ok(ControlFlow::Next),
+                ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
+                ControlFlow::Next | ControlFlow::Continue => (),
+            }
+        }
+
+        match else_block {
+            Some(else_block) => self.run_block(else_block),
+            None => Ok(ControlFlow::Next),
+        }
+    }
+
+    /// while let文を実行する
+    /// valueがnullでない間，その値をnameに束縛したスコープでblockを実行する
+    fn execute_while_let(&mut self, name: &str, value: &Expr, block: &Statement) -> Result<ControlFlow, RuntimeError> {
+        let mut iterations = 0;
+        loop {
+            let value = match self.eval(value)? {
+                Primitive::Null => return Ok(ControlFlow::Next),
+                value => value,
+            };
+            self.count_iteration(&mut iterations)?;
+
+            let mut scope = HashMap::new();
+            scope.insert(name.to_string(), value);
+
+            self.context_mut().blocks.push(scope);
+            let flow = self.run_block(block);
+            self.context_mut().blocks.pop();
+
+            match flow? {
+                ControlFlow::Break(_) => return Ok(ControlFlow::Next),
+                ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
+                ControlFlow::Next | ControlFlow::Continue => (),
+            }
+        }
+    }
+
+    /// loopを実行する
+    /// break，returnするまで本体を繰り返し，そのときの制御フローを返す
+    fn execute_loop(&mut self, block: &Statement) -> Result<ControlFlow, RuntimeError> {
+        let mut iterations = 0;
+        loop {
+            self.count_iteration(&mut iterations)?;
+            match self.run_block(block)? {
+                ControlFlow::Next | ControlFlow::Continue => (),
+                flow => return Ok(flow),
             }
         }
     }
 
+    /// ループの繰り返し回数を数え，上限を超えたらエラーを返す
+    fn count_iteration(&self, iterations: &mut usize) -> Result<(), RuntimeError> {
+        *iterations += 1;
+        match self.iteration_limit {
+            Some(limit) if *iterations > limit => Err(RuntimeError::IterationLimit(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// 式としてのloopを評価する
+    /// breakした値(なければnull)を返す
+    fn eval_loop(&mut self, block: &Statement) -> Result<Primitive, RuntimeError> {
+        match self.execute_loop(block)? {
+            ControlFlow::Break(value) => Ok(value.unwrap_or(Primitive::Null)),
+            _ => Err(RuntimeError::ReturnInExpression),
+        }
+    }
+
+    /// 条件式を評価する
+    fn eval_condition(&mut self, condition: &Expr) -> Result<bool, RuntimeError> {
+        match self.eval(condition)? {
+            Primitive::Boolean(condition) => Ok(condition),
+            condition => Err(RuntimeError::TypeMismatch(format!(
+                "condition must be boolean, found {}",
+                condition
+            ))),
+        }
+    }
+
     /// 式を評価する
-    pub fn eval(&mut self, expr: &Expr) -> Primitive {
+    pub fn eval(&mut self, expr: &Expr) -> Result<Primitive, RuntimeError> {
+        let value = self.eval_expr(expr)?;
+        if let Some(tracer) = &mut self.tracer {
+            tracer(TraceEvent::Eval(expr, &value));
+        }
+
+        Ok(value)
+    }
+
+    fn eval_expr(&mut self, expr: &Expr) -> Result<Primitive, RuntimeError> {
         match expr {
-            Expr::Identifier(name) => self.eval_identifier(name),
-            Expr::Number(n) => Primitive::Number(*n),
+            Expr::Identifier(name) => Ok(self.eval_identifier(name)),
+            Expr::Local(index) => Ok(self.context_mut().slots[*index].clone()),
+            Expr::Number(n) => Ok(Primitive::Number(*n)),
             Expr::PrefixExpr { operator, right } => self.eval_prefix_expr(operator, right),
             Expr::InfixExpr {
                 left,
@@ -95,98 +580,1023 @@ impl Interpreter {
                 // }
                 unimplemented!("postfix operator is not implemented")
             },
-            Expr::String(s) => Primitive::String(s.value.clone()),
+            Expr::String(s) => Ok(Primitive::String(s.value.clone())),
+            Expr::Char(c) => Ok(Primitive::Char(*c)),
+            Expr::Null => Ok(Primitive::Null),
+            Expr::Boolean(b) => Ok(Primitive::Boolean(*b)),
+            Expr::Array(elements) => {
+                Ok(self.eval_list(elements)?.into())
+            }
+            Expr::Tuple(elements) => Ok(Primitive::Tuple(self.eval_list(elements)?)),
+            Expr::Loop(block) => self.eval_loop(block),
+            Expr::Typeof(operand) => {
+                let type_name = self.eval(operand)?.type_name();
+                Ok(Primitive::String(Rc::new(type_name.to_string())))
+            }
+            Expr::Function(function) => Ok(Primitive::Function(Rc::clone(function))),
+            Expr::Call { function, arguments } => self.eval_call(function, arguments),
+            Expr::Index { left, index } => self.eval_index(left, index),
+            Expr::Object(fields) => {
+                let mut object = BTreeMap::new();
+                for (name, value) in fields {
+                    object.insert(name.clone(), self.eval(value)?);
+                }
+                Ok(Primitive::Object(Rc::new(RefCell::new(object))))
+            }
+            Expr::Field { left, name } => {
+                let object = self.eval_object(left)?;
+                let value = object.borrow().get(name).cloned();
+                Ok(value.unwrap_or(Primitive::Null))
+            }
+            Expr::Spread(_) => panic!("spread must appear in an array literal or argument list"),
+            Expr::Conditional { condition, then_value, else_value } => {
+                if self.eval_condition(condition)? {
+                    self.eval(then_value)
+                } else {
+                    self.eval(else_value)
+                }
+            }
         }
     }
 
     fn eval_identifier(&mut self, name: &str) -> Primitive {
-        let value = self.global_context.vars.get(name).unwrap_or(&Primitive::Number(0.0));
-        match value {
-            Primitive::Number(n) => Primitive::Number(*n),
-            Primitive::Boolean(b) => Primitive::Boolean(*b),
-            Primitive::String(s) => Primitive::String(s.clone()),
-            _ => Primitive::Number(0.0)
+        self.lookup(name).cloned().unwrap_or(Primitive::Number(0.0))
+    }
+
+    /// 変数を探す
+    /// 実行中の関数，それを呼び出した関数と呼び出し元へさかのぼり，最後にグローバル変数を探す
+    /// 代入は常に実行中の関数のスコープに行うため，呼び出し元の変数は書き換わらない
+    fn lookup(&self, name: &str) -> Option<&Primitive> {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|context| context.get(name))
+            .or_else(|| self.global_context.get(name))
+    }
+
+    /// 変数に代入する
+    fn set_variable(&mut self, name: &str, value: Primitive) {
+        self.context_mut().assign(name, value);
+    }
+
+    /// 実行中の関数の環境(関数の外ではグローバル環境)を返す
+    fn context_mut(&mut self) -> &mut Context {
+        self.stack.last_mut().unwrap_or(&mut self.global_context)
+    }
+
+    /// 式の列を順に評価する
+    /// 式の列を評価する
+    /// `...expr` の要素はexprの配列の要素に展開する
+    fn eval_list(&mut self, exprs: &[Expr]) -> Result<Vec<Primitive>, RuntimeError> {
+        let mut values = Vec::new();
+        for expr in exprs {
+            let Expr::Spread(array) = expr else {
+                values.push(self.eval(expr)?);
+                continue;
+            };
+
+            match self.eval(array)? {
+                Primitive::Array(array) => values.extend(array.borrow().iter().cloned()),
+                value => {
+                    return Err(RuntimeError::TypeMismatch(format!(
+                        "cannot spread {} {}",
+                        value.type_name(),
+                        value
+                    )))
+                }
+            }
         }
+
+        Ok(values)
     }
 
-    fn eval_prefix_expr(&mut self, operator: &Operator, right: &Expr) -> Primitive {
-        let right = self.eval(right);
-        if let Primitive::Number(right) = right {
-            match operator {
-                Operator::Plus => Primitive::Number(right),
-                Operator::Minus => Primitive::Number(-right),
-                Operator::Not => Primitive::Boolean(right == 0.0),
-                _ => panic!("invalid operator"),
+    fn eval_call(&mut self, function: &Expr, arguments: &[Expr]) -> Result<Primitive, RuntimeError> {
+        if let Expr::Identifier(name) = function {
+            if self.lookup(name).is_none() {
+                return self.call_builtin(name, arguments);
             }
-        } else {
-            panic!("invalid operand")
-        }
-    }
-
-    fn eval_infix_expr(&mut self, left: &Expr, operator: &Operator, right: &Expr) -> Primitive {
-        let l_val = &self.eval(left);
-        let r_val = &self.eval(right);
-        match operator {
-            Operator::Plus => l_val + r_val,
-            Operator::Minus => l_val - r_val,
-            Operator::Mul => l_val * r_val,
-            Operator::Div => l_val / r_val,
-            Operator::Mod => l_val % r_val,
-            Operator::Equal => (l_val == r_val).into(),
-            Operator::ObjectEqual => {
-                if let Primitive::String(l) = l_val {
-                    if let Primitive::String(r) = r_val {
-                        Rc::ptr_eq(l, r).into()
-                    } else {
-                        panic!("invalid type")
-                    }
-                } else {
-                    panic!("invalid type")
+        }
+        if let Expr::Field { left, name } = function {
+            return self.eval_method_call(left, name, arguments);
+        }
+
+        let function = match self.eval(function)? {
+            Primitive::Function(function) => function,
+            value => return Err(RuntimeError::TypeMismatch(format!("{} is not a function", value))),
+        };
+        let arguments = self.eval_list(arguments)?;
+
+        self.call_function(&function, arguments)
+    }
+
+    /// メソッド呼び出し `receiver.name(arguments)` を評価する
+    /// オブジェクトのフィールドが関数であればそれを呼び出し，
+    /// そうでなければ受け手を最初の引数として，受け手の型のメソッドである組み込み関数を呼び出す
+    fn eval_method_call(&mut self, receiver: &Expr, name: &str, arguments: &[Expr]) -> Result<Primitive, RuntimeError> {
+        let receiver = self.eval(receiver)?;
+        if let Primitive::Object(object) = &receiver {
+            let field = object.borrow().get(name).cloned();
+            match field {
+                Some(Primitive::Function(function)) => {
+                    let arguments = self.eval_list(arguments)?;
+                    return self.call_function(&function, arguments);
+                }
+                Some(value) => {
+                    return Err(RuntimeError::TypeMismatch(format!("{} is not a function", value)));
                 }
+                None => (),
             }
-            Operator::NotEqual => (l_val != r_val).into(),
-            Operator::GreaterThan => (l_val > r_val).into(),
-            Operator::GreaterThanEqual => (l_val >= r_val).into(),
-            Operator::LessThan => (l_val < r_val).into(),
-            Operator::LessThanEqual => (l_val <= r_val).into(),
-            Operator::LogicalAnd => l_val.logicaland(&r_val),
-            Operator::LogicalOr => l_val.logicalor(&r_val),
-            Operator::BitAnd => l_val & r_val,
-            Operator::BitOr => l_val| r_val,
-            Operator::Assign => {
-                self.assign(left, r_val);
-                r_val.clone()
-            }
-            Operator::AddAssign => {
-                self.assign(left, &(l_val + r_val));
-                l_val + r_val
-            },
-            Operator::SubAssign => {
-                self.assign(left, &(l_val - r_val));
-                l_val - r_val
-            },
-            Operator::MulAssign => {
-                self.assign(left, &(l_val * r_val));
-                l_val * r_val
-            },
-            Operator::DivAssign => {
-                self.assign(left, &(l_val / r_val));
-                l_val / r_val
-            },
-            Operator::ModAssign => {
-                self.assign(left, &(l_val % r_val));
-                l_val % r_val
-            },
-            _ => panic!("invalid operator"),
+        }
+
+        if !builtins::has_method(receiver.type_name(), name) {
+            return Err(RuntimeError::UndefinedMethod {
+                type_name: receiver.type_name(),
+                name: name.to_string(),
+            });
+        }
+
+        let mut values = vec![receiver];
+        values.extend(self.eval_list(arguments)?);
+        self.call_builtin_with(name, &values)
+    }
+
+    /// 組み込み関数を呼び出す
+    fn call_builtin(&mut self, name: &str, arguments: &[Expr]) -> Result<Primitive, RuntimeError> {
+        if !self.builtins.contains_key(name) && builtins::lookup_higher_order(name).is_none() {
+            return Err(RuntimeError::UndefinedFunction(name.to_string()));
+        }
+
+        let arguments = self.eval_list(arguments)?;
+        self.call_builtin_with(name, &arguments)
+    }
+
+    /// 評価済みの引数で組み込み関数を呼び出す
+    fn call_builtin_with(&mut self, name: &str, arguments: &[Primitive]) -> Result<Primitive, RuntimeError> {
+        if let Some(builtin) = self.builtins.get(name).cloned() {
+            return builtin(arguments);
+        }
+
+        if let Some(builtin) = builtins::lookup_higher_order(name) {
+            return builtin(self, arguments);
+        }
+
+        Err(RuntimeError::UndefinedFunction(name.to_string()))
+    }
+
+    /// 関数を呼び出す
+    pub fn call_function(&mut self, function: &Function, arguments: Vec<Primitive>) -> Result<Primitive, RuntimeError> {
+        if arguments.len() != function.parameters.len() {
+            return Err(RuntimeError::ArgumentCount {
+                name: function.display_name().to_string(),
+                expected: function.parameters.len(),
+                actual: arguments.len(),
+            });
+        }
+
+        self.stack.push(Context::with_arguments(&function.parameters, arguments));
+        let flow = self.execute_body(&function.body);
+        self.stack.pop();
+
+        match flow? {
+            ControlFlow::Next => Ok(Primitive::Null),
+            ControlFlow::Return(value) => Ok(value),
+            ControlFlow::Break(_) => Err(RuntimeError::OutsideLoop("break".to_string())),
+            ControlFlow::Continue => Err(RuntimeError::OutsideLoop("continue".to_string())),
+        }
+    }
+
+    /// 関数の本体を実行する
+    /// 最後の文が式文であれば，その値をreturnしたものとする
+    fn execute_body(&mut self, body: &[Statement]) -> Result<ControlFlow, RuntimeError> {
+        let Some((Statement::Expr(last), statements)) = body.split_last() else {
+            return self.execute(body);
+        };
+
+        match self.execute(statements)? {
+            ControlFlow::Next => Ok(ControlFlow::Return(self.eval(last)?)),
+            flow => Ok(flow),
+        }
+    }
+
+    /// オブジェクトに評価される式を評価する
+    fn eval_object(&mut self, expr: &Expr) -> Result<Rc<RefCell<BTreeMap<String, Primitive>>>, RuntimeError> {
+        match self.eval(expr)? {
+            Primitive::Object(object) => Ok(object),
+            value => Err(RuntimeError::TypeMismatch(format!("{} has no fields", value))),
         }
     }
 
-    fn assign(&mut self, left: &Expr, value: &Primitive) {
-        if let Expr::Identifier(name) = left {
-            self.global_context.vars.insert(name.clone(), value.clone());
+    fn eval_index(&mut self, left: &Expr, index: &Expr) -> Result<Primitive, RuntimeError> {
+        let left = self.eval(left)?;
+        let index = self.eval(index)?;
+
+        let Primitive::Array(array) = &left else {
+            return Err(RuntimeError::TypeMismatch(format!("{} is not indexable", left)));
+        };
+        let Primitive::Number(n) = index else {
+            return Err(RuntimeError::TypeMismatch(format!("index must be number, found {}", index)));
+        };
+
+        // 負の添字は末尾から数える
+        let array = array.borrow();
+        let position = if n < 0.0 { n + array.len() as f64 } else { n };
+        if position < 0.0 || position.fract() != 0.0 || position as usize >= array.len() {
+            return Err(RuntimeError::IndexOutOfRange {
+                index: n,
+                length: array.len(),
+            });
+        }
+
+        Ok(array[position as usize].clone())
+    }
+
+    fn eval_prefix_expr(&mut self, operator: &Operator, right: &Expr) -> Result<Primitive, RuntimeError> {
+        let right = self.eval(right)?;
+        prefix_operation(operator, &right)
+    }
+
+    fn eval_infix_expr(&mut self, left: &Expr, operator: &Operator, right: &Expr) -> Result<Primitive, RuntimeError> {
+        if let Operator::LogicalAnd | Operator::LogicalOr | Operator::Coalesce = operator {
+            return self.eval_logical_expr(left, operator, right);
+        }
+        if let Operator::AndAssign | Operator::OrAssign = operator {
+            return self.eval_logical_assign(left, operator, right);
+        }
+
+        let l_val = &self.eval(left)?;
+        let r_val = &self.eval(right)?;
+        if let Operator::Assign = operator {
+            self.assign(left, r_val)?;
+            return Ok(r_val.clone());
+        }
+        if let Some(operator) = compound_operator(operator) {
+            let value = infix_operation(&operator, l_val, r_val, self.overflow)?;
+            self.assign(left, &value)?;
+            return Ok(value);
+        }
+
+        infix_operation(operator, l_val, r_val, self.overflow)
+    }
+
+    /// 論理演算子式，null合体演算子式を評価する
+    /// 短絡評価を行い，最後に評価したオペランドの値を返す
+    fn eval_logical_expr(&mut self, left: &Expr, operator: &Operator, right: &Expr) -> Result<Primitive, RuntimeError> {
+        let l_val = self.eval(left)?;
+        if short_circuits(operator, &l_val) {
+            Ok(l_val)
         } else {
-            println!("{:?}", left);
-            panic!("invalid left hand side of assignment")
+            self.eval(right)
+        }
+    }
+
+    /// &&=，||=を評価する
+    /// 右辺は左辺の値で結果が決まらないときだけ評価して代入する
+    fn eval_logical_assign(&mut self, left: &Expr, operator: &Operator, right: &Expr) -> Result<Primitive, RuntimeError> {
+        let l_val = self.eval(left)?;
+        if short_circuits(operator, &l_val) {
+            return Ok(l_val);
+        }
+
+        let r_val = self.eval(right)?;
+        self.assign(left, &r_val)?;
+        Ok(r_val)
+    }
+
+    fn assign(&mut self, left: &Expr, value: &Primitive) -> Result<(), RuntimeError> {
+        match left {
+            Expr::Identifier(name) => self.set_variable(name, value.clone()),
+            Expr::Local(index) => self.context_mut().slots[*index] = value.clone(),
+            Expr::Field { left, name } => {
+                let object = self.eval_object(left)?;
+                object.borrow_mut().insert(name.clone(), value.clone());
+            }
+            Expr::Tuple(targets) => self.destructure(targets, value)?,
+            _ => return Err(RuntimeError::InvalidAssignmentTarget),
+        }
+
+        Ok(())
+    }
+
+    /// タプル，配列の各要素を変数に代入する
+    fn destructure(&mut self, targets: &[Expr], value: &Primitive) -> Result<(), RuntimeError> {
+        let values = match value {
+            Primitive::Tuple(values) => values.clone(),
+            Primitive::Array(array) => array.borrow().clone(),
+            _ => return Err(RuntimeError::TypeMismatch(format!("cannot destructure {}", value))),
+        };
+        if targets.len() != values.len() {
+            return Err(RuntimeError::TupleArity {
+                expected: targets.len(),
+                actual: values.len(),
+            });
+        }
+
+        for (target, value) in targets.iter().zip(&values) {
+            self.assign(target, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// トップレベルのreturnやmain関数の返り値を終了コードに変換する
+/// 0から255までの整数でなければエラーになる
+pub fn exit_code(value: &Primitive) -> Result<i32, RuntimeError> {
+    match value {
+        Primitive::Number(n) if n.fract() == 0.0 && (0.0..=255.0).contains(n) => Ok(*n as i32),
+        _ => Err(RuntimeError::InvalidExitCode(value.to_string())),
+    }
+}
+
+/// in演算子の結果を返す
+/// 配列なら等しい(==)要素があるか，文字列なら部分文字列であるかどうか
+fn contains(container: &Primitive, value: &Primitive) -> Result<bool, RuntimeError> {
+    match (container, value) {
+        (Primitive::Array(array), _) => Ok(array.borrow().iter().any(|element| values_equal(element, value))),
+        (Primitive::String(s), Primitive::String(sub)) => Ok(s.contains(sub.as_str())),
+        (Primitive::String(s), Primitive::Char(c)) => Ok(s.contains(*c)),
+        (Primitive::String(_), _) => Err(RuntimeError::TypeMismatch(format!(
+            "cannot search for {} in string",
+            value.type_name()
+        ))),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "right operand of in must be array or string, found {}",
+            container.type_name()
+        ))),
+    }
+}
+
+/// 前置演算子を値に適用する
+pub fn prefix_operation(operator: &Operator, right: &Primitive) -> Result<Primitive, RuntimeError> {
+    match (operator, right) {
+        (Operator::Not, _) => Ok(Primitive::Boolean(!right.is_truthy())),
+        (Operator::Plus, Primitive::Number(n)) => Ok(Primitive::Number(*n)),
+        (Operator::Minus, Primitive::Number(_)) => Ok(-right),
+        (Operator::Plus | Operator::Minus, _) => {
+            let symbol = if *operator == Operator::Plus { "+" } else { "-" };
+            Err(RuntimeError::TypeMismatch(format!(
+                "cannot apply unary {} to {} {}",
+                symbol,
+                right.type_name(),
+                right
+            )))
+        }
+        _ => panic!("invalid operator"),
+    }
+}
+
+/// print文で表示する文字列に値を変換する
+/// precisionがあれば，配列などの要素も含めて数値を小数点以下precision桁で表示する
+pub fn render(value: &Primitive, precision: Option<usize>) -> String {
+    let Some(precision) = precision else {
+        return value.to_string();
+    };
+
+    // 要素の文字列，文字は区別できるように引用符で囲む
+    let render_element = |element: &Primitive| match element {
+        Primitive::String(_) | Primitive::Char(_) => element.debug_display(),
+        _ => render(element, Some(precision)),
+    };
+
+    match value {
+        Primitive::Number(n) if n.is_finite() => format!("{:.*}", precision, n),
+        Primitive::Array(elements) => {
+            let elements = elements.borrow().iter().map(render_element).collect::<Vec<_>>();
+            format!("[{}]", elements.join(", "))
+        }
+        Primitive::Tuple(elements) => {
+            let elements = elements.iter().map(render_element).collect::<Vec<_>>();
+            format!("({})", elements.join(", "))
+        }
+        Primitive::Object(object) => {
+            let fields = object
+                .borrow()
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, render_element(value)))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", fields.join(", "))
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// 中置演算子を2つの値に適用する
+/// 代入演算子と短絡評価する演算子は扱わない
+pub fn infix_operation(operator: &Operator, l_val: &Primitive, r_val: &Primitive, overflow: Overflow) -> Result<Primitive, RuntimeError> {
+    Ok(match operator {
+        Operator::Plus | Operator::Minus | Operator::Mul | Operator::Div | Operator::Mod => {
+            checked_arith(operator, l_val, r_val)?
         }
+        // IEEE 754に従い，NaN == NaN は偽になる (NaNの判定にはisnanを使う)
+        Operator::Equal => values_equal(l_val, r_val).into(),
+        Operator::ObjectEqual => match (l_val, r_val) {
+            (Primitive::String(l), Primitive::String(r)) => Rc::ptr_eq(l, r).into(),
+            (Primitive::Array(l), Primitive::Array(r)) => Rc::ptr_eq(l, r).into(),
+            (Primitive::Function(l), Primitive::Function(r)) => Rc::ptr_eq(l, r).into(),
+            (Primitive::Object(l), Primitive::Object(r)) => Rc::ptr_eq(l, r).into(),
+            // 数値は参照を持たないため，===では常に偽になる (2 === 2.0 も偽，数値の比較には==を使う)
+            (Primitive::Number(_), Primitive::Number(_)) => false.into(),
+            _ => (l_val == r_val).into(),
+        },
+        Operator::NotEqual => (!values_equal(l_val, r_val)).into(),
+        Operator::GreaterThan | Operator::GreaterThanEqual | Operator::LessThan | Operator::LessThanEqual => {
+            compare(operator, l_val, r_val)?
+        }
+        Operator::In => contains(r_val, l_val)?.into(),
+        Operator::BitAnd | Operator::BitOr | Operator::ShiftLeft | Operator::ShiftRight => {
+            integer_operation(operator, l_val, r_val, overflow)?
+        }
+        _ => panic!("invalid operator"),
+    })
+}
+
+/// ==で2つの値を比較する
+/// 同じ領域を指す文字列 (インターンされた同じ内容のリテラルなど) は内容を比較しない
+/// 文字と文字列は別の型なので，同じ1文字の文字列とも等しくない
+/// 配列，タプル，オブジェクトは要素ごとに==で比較する
+pub fn values_equal(l_val: &Primitive, r_val: &Primitive) -> bool {
+    values_equal_visiting(l_val, r_val, &mut Vec::new())
+}
+
+/// visitedは比較中の配列，オブジェクトの組 (循環していれば等しいとみなす)
+fn values_equal_visiting(l_val: &Primitive, r_val: &Primitive, visited: &mut Vec<(usize, usize)>) -> bool {
+    let all_equal = |l: &[Primitive], r: &[Primitive], visited: &mut Vec<(usize, usize)>| {
+        l.len() == r.len() && l.iter().zip(r).all(|(l, r)| values_equal_visiting(l, r, visited))
+    };
+
+    match (l_val, r_val) {
+        (Primitive::String(l), Primitive::String(r)) => Rc::ptr_eq(l, r) || l == r,
+        (Primitive::Array(l), Primitive::Array(r)) => {
+            let pair = (Rc::as_ptr(l) as usize, Rc::as_ptr(r) as usize);
+            if Rc::ptr_eq(l, r) || visited.contains(&pair) {
+                return true;
+            }
+
+            visited.push(pair);
+            all_equal(&l.borrow(), &r.borrow(), visited)
+        }
+        (Primitive::Tuple(l), Primitive::Tuple(r)) => all_equal(l, r, visited),
+        (Primitive::Object(l), Primitive::Object(r)) => {
+            let pair = (Rc::as_ptr(l) as usize, Rc::as_ptr(r) as usize);
+            if Rc::ptr_eq(l, r) || visited.contains(&pair) {
+                return true;
+            }
+
+            visited.push(pair);
+            let (l, r) = (l.borrow(), r.borrow());
+            l.len() == r.len()
+                && l.iter().zip(r.iter()).all(|((l_name, l), (r_name, r))| {
+                    l_name == r_name && values_equal_visiting(l, r, visited)
+                })
+        }
+        _ => l_val == r_val,
+    }
+}
+
+/// 大小比較 (>，>=，<，<=) を行う
+/// 比較できるのは数値同士，文字列同士，文字同士だけで，それ以外はTypeMismatchになる
+fn compare(operator: &Operator, l_val: &Primitive, r_val: &Primitive) -> Result<Primitive, RuntimeError> {
+    match (l_val, r_val) {
+        (Primitive::Number(_), Primitive::Number(_))
+        | (Primitive::String(_), Primitive::String(_))
+        | (Primitive::Char(_), Primitive::Char(_)) => (),
+        _ => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "cannot compare {} and {}",
+                l_val.type_name(),
+                r_val.type_name()
+            )))
+        }
+    }
+
+    Ok(match operator {
+        Operator::GreaterThan => l_val > r_val,
+        Operator::GreaterThanEqual => l_val >= r_val,
+        Operator::LessThan => l_val < r_val,
+        _ => l_val <= r_val,
+    }
+    .into())
+}
+
+/// +，-，*，/，%を計算する
+/// 数値と真偽値の組み合わせ，または文字列同士の+以外はTypeMismatchになる
+fn checked_arith(operator: &Operator, l_val: &Primitive, r_val: &Primitive) -> Result<Primitive, RuntimeError> {
+    let valid = match (l_val, r_val) {
+        (Primitive::String(_), Primitive::String(_)) => *operator == Operator::Plus,
+        _ => l_val.arithmetic_operand().is_some() && r_val.arithmetic_operand().is_some(),
+    };
+    if !valid {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "arithmetic operation on {} and {}",
+            l_val.type_name(),
+            r_val.type_name()
+        )));
+    }
+
+    Ok(match operator {
+        Operator::Plus => l_val + r_val,
+        Operator::Minus => l_val - r_val,
+        Operator::Mul => l_val * r_val,
+        Operator::Div => l_val / r_val,
+        _ => l_val % r_val,
+    })
+}
+
+/// ビット演算，シフト演算を32ビット整数で行う
+fn integer_operation(operator: &Operator, l_val: &Primitive, r_val: &Primitive, overflow: Overflow) -> Result<Primitive, RuntimeError> {
+    let (Primitive::Number(l), Primitive::Number(r)) = (l_val, r_val) else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "integer operation on {} and {}",
+            l_val.type_name(),
+            r_val.type_name()
+        )));
+    };
+
+    let symbol = match operator {
+        Operator::BitAnd => "&",
+        Operator::BitOr => "|",
+        Operator::ShiftLeft => "<<",
+        _ => ">>",
+    };
+    let expression = || format!("{} {} {}", l, symbol, r);
+
+    let left = overflow.to_i32(*l, expression)?;
+    let right = overflow.to_i32(*r, expression)?;
+    let value = match (operator, overflow) {
+        (Operator::BitAnd, _) => left & right,
+        (Operator::BitOr, _) => left | right,
+        (Operator::ShiftLeft, Overflow::Wrapping) => left.wrapping_shl(*r as u32),
+        (Operator::ShiftRight, Overflow::Wrapping) => left.wrapping_shr(*r as u32),
+        (_, Overflow::Checked) if right < 0 => return Err(RuntimeError::IntegerOverflow(expression())),
+        // 32ビット以上の右シフトは符号ビットで埋まる
+        (Operator::ShiftRight, _) => left >> right.clamp(0, 31),
+        (_, _) if left == 0 => 0,
+        (_, _) => overflow.to_i32(left as f64 * 2f64.powi(right.max(0)), expression)?,
+    };
+
+    Ok(Primitive::Number(value as f64))
+}
+
+/// 複合代入演算子に対応する演算子を返す
+/// 複合代入演算子でなければNone
+pub fn compound_operator(operator: &Operator) -> Option<Operator> {
+    match operator {
+        Operator::AddAssign => Some(Operator::Plus),
+        Operator::SubAssign => Some(Operator::Minus),
+        Operator::MulAssign => Some(Operator::Mul),
+        Operator::DivAssign => Some(Operator::Div),
+        Operator::ModAssign => Some(Operator::Mod),
+        Operator::ShiftLeftAssign => Some(Operator::ShiftLeft),
+        Operator::ShiftRightAssign => Some(Operator::ShiftRight),
+        _ => None,
+    }
+}
+
+/// 左辺の値だけで&&，||，??(と&&=，||=)の結果が決まるかどうかを返す
+pub fn short_circuits(operator: &Operator, l_val: &Primitive) -> bool {
+    match operator {
+        Operator::LogicalAnd | Operator::AndAssign => !l_val.is_truthy(),
+        Operator::LogicalOr | Operator::OrAssign => l_val.is_truthy(),
+        Operator::Coalesce => *l_val != Primitive::Null,
+        _ => panic!("invalid operator"),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::token::Lexer;
+
+    /// コードを構文解析して新しいインタプリタで実行し，最後の式文の値を返す
+    pub fn eval(code: &str) -> Result<Primitive, RuntimeError> {
+        eval_with(&mut Interpreter::new(), code)
+    }
+
+    /// コードを構文解析してinterpreterで実行し，最後の式文の値を返す
+    pub fn eval_with(interpreter: &mut Interpreter, code: &str) -> Result<Primitive, RuntimeError> {
+        let program = Parser::new(Lexer::new(code)).parse().expect("failed to parse");
+        interpreter.run(&program)
+    }
+
+    /// コードを実行し，エラーメッセージを返す
+    pub fn eval_error(code: &str) -> String {
+        eval(code).expect_err("expected runtime error").to_string()
+    }
+
+    #[test]
+    fn unicode_variable_names() {
+        assert_eq!(eval("数値 = 3\nπ = 0.5\n数値 + π"), Ok(Primitive::Number(3.5)));
+    }
+
+    #[test]
+    fn logical_operators_return_operand() {
+        assert_eq!(eval("(0 || 5) == 5"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("(3 && \"hi\") == \"hi\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("(\"\" || 0) == 0"), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(eval("0 && error(\"evaluated\")"), Ok(Primitive::Number(0.0)));
+        assert_eq!(eval("1 || error(\"evaluated\")"), Ok(Primitive::Number(1.0)));
+    }
+
+    #[test]
+    fn identical_string_literals_are_interned() {
+        assert_eq!(eval("\"a\" === \"a\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("(\"a\" + \"\") === \"a\""), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("\"a\" == \"a\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("(\"a\" + \"\") == \"a\""), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn while_else_runs_when_loop_finishes() {
+        assert_eq!(eval("i = 0\nr = 0\nwhile i < 3 { i += 1 } else { r = 1 }\nr"), Ok(Primitive::Number(1.0)));
+        assert_eq!(eval("r = 0\nwhile 1 > 2 { r = 2 } else { r = 1 }\nr"), Ok(Primitive::Number(1.0)));
+    }
+
+    #[test]
+    fn while_else_skipped_after_break() {
+        assert_eq!(eval("i = 0\nr = 0\nwhile i < 3 { break } else { r = 1 }\nr"), Ok(Primitive::Number(0.0)));
+    }
+
+    #[test]
+    fn coalesce_takes_right_only_for_null() {
+        assert_eq!(eval("null ?? 5"), Ok(Primitive::Number(5.0)));
+        assert_eq!(eval("3 ?? 5"), Ok(Primitive::Number(3.0)));
+        assert_eq!(eval("3 ?? error(\"evaluated\")"), Ok(Primitive::Number(3.0)));
+    }
+
+    #[test]
+    fn braceless_bodies() {
+        assert_eq!(eval("r = 0\nif 1 > 0 r = 1\nr"), Ok(Primitive::Number(1.0)));
+        assert_eq!(eval("i = 0\nwhile i < 3 i += 1\ni"), Ok(Primitive::Number(3.0)));
+    }
+
+    #[test]
+    fn dangling_else_binds_to_inner_if() {
+        assert_eq!(eval("r = 0\nif 1 > 0 if 1 > 2 r = 5 else r = 6\nr"), Ok(Primitive::Number(6.0)));
+        assert_eq!(eval("r = 0\nif 1 > 2 if 1 > 0 r = 5 else r = 6\nr"), Ok(Primitive::Number(0.0)));
+    }
+
+    #[test]
+    fn blocks_discard_their_variables() {
+        assert_eq!(eval("{\n  inner = 1\n}\ninner"), Ok(Primitive::Number(0.0)));
+        assert_eq!(eval("if 1 > 0 {\n  inner = 1\n}\ninner"), Ok(Primitive::Number(0.0)));
+        assert_eq!(eval("outer = 1\n{\n  outer = 2\n}\nouter"), Ok(Primitive::Number(2.0)));
+    }
+
+    #[test]
+    fn loop_breaks_with_value() {
+        assert_eq!(eval("i = 0\nloop {\n  i += 1\n  if i == 5 { break }\n}\ni"), Ok(Primitive::Number(5.0)));
+        assert_eq!(eval("i = 0\nx = loop { if i > 3 { break i } i = i + 1 }\nx"), Ok(Primitive::Number(4.0)));
+    }
+
+    #[test]
+    fn subtraction_without_spaces() {
+        assert_eq!(eval("x = 5-1\nx"), Ok(Primitive::Number(4.0)));
+        assert_eq!(eval("x = 3\nx-1"), Ok(Primitive::Number(2.0)));
+        assert_eq!(eval("x = 2 * -1\nx"), Ok(Primitive::Number(-2.0)));
+    }
+
+    #[test]
+    fn integers_and_floats_compare_as_numbers() {
+        assert_eq!(eval("2 == 2.0"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("2 < 2.5"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("\"a\" < \"b\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("'b' >= 'a'"), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn comparison_rejects_mixed_types() {
+        assert_eq!(eval_error("1 < \"a\""), "type mismatch: cannot compare number and string");
+        assert_eq!(eval_error("null > 5"), "type mismatch: cannot compare null and number");
+        assert_eq!(eval_error("'a' <= \"a\""), "type mismatch: cannot compare char and string");
+    }
+
+    #[test]
+    fn strict_equality_rejects_numbers() {
+        assert_eq!(eval("2 === 2.0"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("x = 2\nx === x"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("2 === \"2\""), Ok(Primitive::Boolean(false)));
+    }
+
+    #[test]
+    fn registered_builtin_is_callable() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register_builtin("double", |args: &[Primitive]| match args {
+            [Primitive::Number(n)] => Ok(Primitive::Number(n * 2.0)),
+            _ => Err(RuntimeError::InvalidArgument("double: expected one number".to_string())),
+        });
+
+        assert_eq!(eval_with(&mut interpreter, "double(21)"), Ok(Primitive::Number(42.0)));
+        assert_eq!(
+            eval_with(&mut interpreter, "double()").unwrap_err().to_string(),
+            "invalid argument: double: expected one number"
+        );
+    }
+
+    #[test]
+    fn typeof_distinguishes_arrays_and_functions() {
+        assert_eq!(eval("typeof [1, 2] == \"array\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("typeof (fn() {}) == \"function\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("typeof 1 == \"number\""), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn boolean_literals_coerce_in_arithmetic() {
+        assert_eq!(eval("true"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("false"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("true + true == 2"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("true * 3 == 3"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("false - 1 == -1"), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn arithmetic_rejects_other_types() {
+        assert_eq!(eval_error("\"a\" + 1"), "type mismatch: arithmetic operation on string and number");
+        assert_eq!(eval_error("\"a\" - \"b\""), "type mismatch: arithmetic operation on string and string");
+        assert_eq!(eval_error("[1] * 2"), "type mismatch: arithmetic operation on array and number");
+        assert_eq!(eval_error("null % 2"), "type mismatch: arithmetic operation on null and number");
+        assert_eq!(eval_error("x = \"a\"\nx += 1"), "type mismatch: arithmetic operation on string and number");
+    }
+
+    #[test]
+    fn logical_assignment_short_circuits() {
+        assert_eq!(eval("x = true\nx &&= false\nx"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("x = false\nx &&= error(\"evaluated\")\nx"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("x = false\nx ||= true\nx"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("x = true\nx ||= error(\"evaluated\")\nx"), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn not_applies_truthiness() {
+        assert_eq!(eval("!true == false"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("!false"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("!0"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("!2"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("!\"\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("!\"x\""), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("!null"), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn parameter_shadows_global_during_call() {
+        assert_eq!(eval("x = 1\nfn f(x) { x * 10 }\nf(5)"), Ok(Primitive::Number(50.0)));
+        assert_eq!(eval("x = 1\nfn f(x) { x * 10 }\nf(5)\nx"), Ok(Primitive::Number(1.0)));
+        assert_eq!(eval("x = 1\nfn f(x) { x = x + 1\nx }\nf(5) + x"), Ok(Primitive::Number(7.0)));
+    }
+
+    #[test]
+    fn negative_index_counts_from_end() {
+        assert_eq!(eval("[10, 20, 30][-1] == 30"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("[10, 20, 30][-3] == 10"), Ok(Primitive::Boolean(true)));
+        assert!(eval("[10][-2]").is_err());
+        assert!(eval("[10][0.5]").is_err());
+    }
+
+    #[test]
+    fn tuple_return_destructures() {
+        assert_eq!(eval("fn f() { return 1, 2 }\nx, y = f()\nx * 10 + y"), Ok(Primitive::Number(12.0)));
+        assert!(eval_error("fn f() { return 1, 2 }\na, b, c = f()").contains("cannot destructure 2 value(s) into 3"));
+    }
+
+    #[test]
+    fn array_destructuring() {
+        assert_eq!(eval("a, b, c = [1, 2, 3]\na * 100 + b * 10 + c"), Ok(Primitive::Number(123.0)));
+        assert!(eval_error("a, b = [1, 2, 3]").contains("cannot destructure 3 value(s) into 2"));
+        assert_eq!(eval("a = [1, 2]\nlen(a)"), Ok(Primitive::Number(2.0)));
+    }
+
+    #[test]
+    fn last_expression_is_implicit_return() {
+        assert_eq!(eval("fn sq(x) { x * x }\nsq(4)"), Ok(Primitive::Number(16.0)));
+        assert_eq!(eval("fn f(x) {\nif x > 0 { return 1 }\n2\n}\nf(5) * 10 + f(-5)"), Ok(Primitive::Number(12.0)));
+        assert_eq!(eval("fn f() {}\nf()"), Ok(Primitive::Null));
+    }
+
+    #[test]
+    fn tracer_records_statements_and_evaluations() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new();
+        let recorded = Rc::clone(&events);
+        interpreter.set_tracer(move |event| {
+            recorded.borrow_mut().push(match event {
+                TraceEvent::Statement(statement) => format!("statement {:?}", statement),
+                TraceEvent::Eval(expr, value) => format!("{:?} => {}", expr, value),
+            })
+        });
+
+        eval_with(&mut interpreter, "print 1 + 2").unwrap();
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                "statement Print([InfixExpr { left: Number(1.0), operator: Plus, right: Number(2.0) }])",
+                "Number(1.0) => 1",
+                "Number(2.0) => 2",
+                "InfixExpr { left: Number(1.0), operator: Plus, right: Number(2.0) } => 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn iteration_limit_stops_infinite_loop() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_iteration_limit(Some(100));
+        assert_eq!(eval_with(&mut interpreter, "loop { }"), Err(RuntimeError::IterationLimit(100)));
+        assert!(eval_with(&mut interpreter, "while true { }").is_err());
+        assert_eq!(eval_with(&mut interpreter, "i = 0\nwhile i < 10 { i += 1 }\ni"), Ok(Primitive::Number(10.0)));
+    }
+
+    #[test]
+    fn unary_operators_reject_non_numbers() {
+        assert_eq!(eval_error("-true"), "type mismatch: cannot apply unary - to boolean true");
+        assert_eq!(eval_error("-\"x\""), "type mismatch: cannot apply unary - to string x");
+        assert_eq!(eval_error("+\"x\""), "type mismatch: cannot apply unary + to string x");
+    }
+
+    #[test]
+    fn try_catch_binds_error_message() {
+        assert_eq!(
+            eval("r = 0\ntry { error(\"boom\") } catch (e) { r = e }\nr == \"error: boom\""),
+            Ok(Primitive::Boolean(true))
+        );
+        assert_eq!(eval("r = 0\ntry { [1][5] } catch (e) { r = typeof(e) }\nr == \"string\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("r = 0\ntry { x = 1 } catch (e) { r = 1 }\nr"), Ok(Primitive::Number(0.0)));
+        assert_eq!(
+            eval("r = 0\ntry { x = \"a\" + 1 } catch (e) { r = e }\nr == \"type mismatch: arithmetic operation on string and number\""),
+            Ok(Primitive::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn in_operator() {
+        assert_eq!(eval("3 in [1, 2, 3]"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("4 in [1, 2, 3]"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("\"ell\" in \"hello\""), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("\"xyz\" in \"hello\""), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval_error("1 in 2"), "type mismatch: right operand of in must be array or string, found number");
+    }
+
+    #[test]
+    fn arrays_display_recursively() {
+        let display = |code| eval(code).unwrap().to_string();
+        assert_eq!(display("[1, 2, 3]"), "[1, 2, 3]");
+        assert_eq!(display("[1, [2, [3]], []]"), "[1, [2, [3]], []]");
+        assert_eq!(display("[\"a\", 'b', [\"c\"]]"), "[\"a\", 'b', [\"c\"]]");
+        assert_eq!(display("\"top\""), "top");
+    }
+
+    #[test]
+    fn logical_operators_accept_every_operand_type() {
+        // (リテラル, 真と見なされるか)
+        let operands = [
+            ("true", true),
+            ("false", false),
+            ("1", true),
+            ("0", false),
+            ("\"x\"", true),
+            ("\"\"", false),
+            ("null", false),
+        ];
+
+        for (left, left_truthy) in operands {
+            for (right, _) in operands {
+                let (and, or) = if left_truthy { (right, left) } else { (left, right) };
+                assert_eq!(eval(&format!("{} && {}", left, right)), eval(and), "{} && {}", left, right);
+                assert_eq!(eval(&format!("{} || {}", left, right)), eval(or), "{} || {}", left, right);
+            }
+        }
+    }
+
+    #[test]
+    fn object_fields() {
+        assert_eq!(eval("p = { x: 1, y: 2 }\np.x + p.y"), Ok(Primitive::Number(3.0)));
+        assert_eq!(eval("p = { x: 1 }\np.x = 3\np.x"), Ok(Primitive::Number(3.0)));
+        assert_eq!(eval("p = { x: 1 }\nq = p\nq.x = 5\np.x"), Ok(Primitive::Number(5.0)));
+        assert_eq!(eval("p = { x: 1, y: 2 }").unwrap().to_string(), "{x: 1, y: 2}");
+    }
+
+    #[test]
+    fn string_equality_compares_content_and_strict_equality_compares_identity() {
+        assert_eq!(eval("a = \"x\" + \"y\"\nb = \"x\" + \"y\"\na == b"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("a = \"x\" + \"y\"\nb = \"x\" + \"y\"\na === b"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("a = \"x\" + \"y\"\nb = a\na === b"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("\"xy\" == \"yx\""), Ok(Primitive::Boolean(false)));
+    }
+
+    #[test]
+    fn overflow_modes() {
+        let eval_in = |overflow, code| {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_overflow(overflow);
+            eval_with(&mut interpreter, code)
+        };
+
+        assert_eq!(eval_in(Overflow::Wrapping, "2147483647 << 1"), Ok(Primitive::Number(-2.0)));
+        assert_eq!(eval_in(Overflow::Saturating, "2147483647 << 1"), Ok(Primitive::Number(2147483647.0)));
+        assert_eq!(
+            eval_in(Overflow::Checked, "2147483647 << 1"),
+            Err(RuntimeError::IntegerOverflow("2147483647 << 1".to_string()))
+        );
+        assert_eq!(eval_in(Overflow::Wrapping, "4294967296 | 0"), Ok(Primitive::Number(0.0)));
+        assert!(eval_in(Overflow::Checked, "4294967296 | 0").is_err());
+    }
+
+    #[test]
+    fn unary_operator_chains() {
+        assert_eq!(eval("- -5 == 5"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("-+-3 == 3"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("+ - + 3 == -3"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("- - - 2"), Ok(Primitive::Number(-2.0)));
+    }
+
+    #[test]
+    fn while_let_loops_until_null() {
+        let code = "c = { n: 0 }\nfn next() { if c.n < 3 { c.n = c.n + 1\nreturn c.n }\nnull }\ntotal = 0\nwhile let x = next() { total = total + x }\ntotal";
+        assert_eq!(eval(code), Ok(Primitive::Number(6.0)));
+        assert_eq!(eval("n = 0\nwhile let x = null { n = 1 }\nn"), Ok(Primitive::Number(0.0)));
+    }
+
+    #[test]
+    fn run_returning_collects_expression_values() {
+        let program = |code| Parser::new(Lexer::new(code)).parse().expect("failed to parse");
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(
+            interpreter.run_returning(&program("1 + 1\nx = 3\nif x > 0 { x }\nx * 2")),
+            Ok(vec![Primitive::Number(2.0), Primitive::Number(3.0), Primitive::Number(6.0)])
+        );
+
+        assert_eq!(
+            interpreter.run_returning(&program("y = 1\nerror(\"stop\")\ny = 2")),
+            Err(RuntimeError::UserError("stop".to_string()))
+        );
+        assert_eq!(interpreter.global("y"), Some(&Primitive::Number(1.0)));
+    }
+
+    #[test]
+    fn string_equality_checks_identity_first() {
+        let long = Rc::new("x".repeat(100_000));
+        assert!(values_equal(&Primitive::String(Rc::clone(&long)), &Primitive::String(Rc::clone(&long))));
+        assert!(values_equal(&Primitive::String(long), &Primitive::String(Rc::new("x".repeat(100_000)))));
+        assert!(!values_equal(&Primitive::String(Rc::new("x".to_string())), &Primitive::String(Rc::new("y".to_string()))));
+        assert_eq!(eval("s = repeat(\"ab\", 1000)\nt = s\ns == t && s == repeat(\"ab\", 1000)"), Ok(Primitive::Boolean(true)));
+    }
+
+    #[test]
+    fn render_every_kind() {
+        let mut interpreter = Interpreter::new();
+        let render = |interpreter: &mut Interpreter, code| {
+            let value = eval_with(interpreter, code).unwrap();
+            interpreter.render(&value)
+        };
+
+        assert_eq!(render(&mut interpreter, "1.5"), "1.5");
+        assert_eq!(render(&mut interpreter, "true"), "true");
+        assert_eq!(render(&mut interpreter, "\"text\""), "text");
+        assert_eq!(render(&mut interpreter, "'c'"), "c");
+        assert_eq!(render(&mut interpreter, "null"), "null");
+        assert_eq!(render(&mut interpreter, "[1, \"a\", [null]]"), "[1, \"a\", [null]]");
+
+        interpreter.set_precision(Some(2));
+        assert_eq!(render(&mut interpreter, "1 / 3"), "0.33");
+        assert_eq!(render(&mut interpreter, "[2, \"a\"]"), "[2.00, \"a\"]");
+        assert_eq!(render(&mut interpreter, "false"), "false");
+    }
+
+    #[test]
+    fn elif_chains() {
+        let code = |x| format!("x = {}\nr = 0\nif x < 0 {{ r = 1 }} elif x == 0 {{ r = 2 }} elif x < 10 {{ r = 3 }} else {{ r = 4 }}\nr", x);
+        assert_eq!(eval(&code(-5)), Ok(Primitive::Number(1.0)));
+        assert_eq!(eval(&code(0)), Ok(Primitive::Number(2.0)));
+        assert_eq!(eval(&code(5)), Ok(Primitive::Number(3.0)));
+        assert_eq!(eval(&code(50)), Ok(Primitive::Number(4.0)));
+        assert_eq!(eval("r = 0\nif false { r = 1 } elif true { r = 2 } else if true { r = 3 }\nr"), Ok(Primitive::Number(2.0)));
+    }
+
+    #[test]
+    fn conditional_values() {
+        assert_eq!(eval("x = true ? 2 : 3\nx"), Ok(Primitive::Number(2.0)));
+        assert_eq!(eval("false ? 1 : false ? 2 : 3"), Ok(Primitive::Number(3.0)));
+        assert_eq!(eval("c = 0\nfalse ? 1 : c = 5\nc"), Ok(Primitive::Number(5.0)));
+    }
+
+    #[test]
+    fn spread_into_literals_and_calls() {
+        assert_eq!(eval("a = [1, 2, 3]\n[0, ...a, 4] == [0, 1, 2, 3, 4]"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("fn add(a, b, c) { a + b + c }\nargs = [1, 2]\nadd(...args, 3)"), Ok(Primitive::Number(6.0)));
+        assert!(eval("[...1]").is_err());
+        assert!(eval("fn f(a) { a }\nf(...\"ab\")").is_err());
+    }
+
+    #[test]
+    fn is_tests_type() {
+        assert_eq!(eval("5 is number"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("\"a\" is string"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("5 is string"), Ok(Primitive::Boolean(false)));
+        assert_eq!(eval("true is boolean && null is null"), Ok(Primitive::Boolean(true)));
+        assert_eq!(eval("r = 0\nx = [1]\nif x is array { r = 1 }\nr"), Ok(Primitive::Number(1.0)));
     }
 }
@@ -1,56 +1,528 @@
-use std::{io::{self, Write, Read}, env::args, fs::File};
+use std::{io::{self, BufRead, Read, Write}, env::args, fs::File, path::Path, process::exit, time::Instant};
 
-use crate::{interpreter::Interpreter, parse::Parser, token::Lexer};
+use crate::{interpreter::{Interpreter, Overflow, TraceEvent}, parse::{Parser, Statement}, vm::Vm, token::{Lexer, Warning}, types::{Primitive, TypeName}};
 
+mod ast_json;
+mod builtins;
+mod compile;
+mod include;
 mod interpreter;
+mod optimize;
 mod parse;
+mod resolve;
 mod token;
 mod types;
+mod vm;
+
+/// コマンドライン引数
+#[derive(Debug, Clone, Default)]
+struct Options {
+    /// 実行せずに構文だけを検査する
+    check: bool,
+    /// 実行せずに構文木をJSONで表示する
+    ast_json: bool,
+    /// 実行前に定数畳み込みを行う
+    optimize: bool,
+    /// 警告を表示する
+    warnings: bool,
+    /// 警告があれば実行しない
+    strict: bool,
+    /// 実行時間を表示する
+    time: bool,
+    /// 実行した文と評価した式を標準エラー出力に表示する
+    trace: bool,
+    /// 1つのループで繰り返せる回数の上限
+    max_iterations: Option<usize>,
+    /// ビット演算，シフト演算で値が範囲を超えたときの振る舞い
+    overflow: Overflow,
+    /// print文で数値を表示するときの小数点以下の桁数
+    precision: Option<usize>,
+    /// バイトコードにコンパイルして実行する
+    vm: bool,
+    /// ファイルの代わりに実行するコード
+    eval: Option<String>,
+    /// REPLのプロンプト (Noneなら">> ")
+    prompt: Option<String>,
+    /// REPLでプロンプトと式の値を表示しない
+    quiet: bool,
+    /// 実行するファイル
+    file_path: Option<String>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--check" => options.check = true,
+                "--ast-json" => options.ast_json = true,
+                "-O" | "--optimize" => options.optimize = true,
+                "-W" | "--warnings" => options.warnings = true,
+                "--strict" => options.strict = true,
+                "--time" => options.time = true,
+                "--trace" => options.trace = true,
+                "--vm" => options.vm = true,
+                "-q" | "--quiet" => options.quiet = true,
+                "--eval" => options.eval = Some(args.next().expect("--eval requires code")),
+                "--overflow=wrapping" => options.overflow = Overflow::Wrapping,
+                "--overflow=saturating" => options.overflow = Overflow::Saturating,
+                "--overflow=checked" => options.overflow = Overflow::Checked,
+                _ if arg.starts_with("--prompt=") => options.prompt = Some(arg["--prompt=".len()..].to_string()),
+                _ if arg.starts_with("--precision=") => {
+                    let value = &arg["--precision=".len()..];
+                    let precision = value.parse().unwrap_or_else(|_| panic!("invalid precision: {}", value));
+                    options.precision = Some(precision);
+                }
+                _ if arg.starts_with("--max-iterations=") => {
+                    let value = &arg["--max-iterations=".len()..];
+                    let limit = value.parse().unwrap_or_else(|_| panic!("invalid iteration limit: {}", value));
+                    options.max_iterations = Some(limit);
+                }
+                _ => options.file_path = Some(arg),
+            }
+        }
+
+        options
+    }
+}
 
 fn main() {
+    let options = Options::parse(args().skip(1));
+    let mut interpreter = new_interpreter(&options);
+
+    if let Some(code) = &options.eval {
+        eval_code(&mut interpreter, code, &options);
+        return;
+    }
+
+    match &options.file_path {
+        Some(file_path) if options.check => exit(check_file(file_path, &options)),
+        Some(file_path) if options.ast_json => exit(print_ast_json(file_path)),
+        Some(file_path) => run_file(&mut interpreter, file_path, &options),
+        None if options.check => panic!("no file path"),
+        None if options.ast_json => panic!("--ast-json requires a file path"),
+        None if options.vm => panic!("--vm requires a file path"),
+        None => repl(&mut interpreter, &options, &mut io::stdin().lock(), &mut io::stdout())
+            .expect("failed to read line"),
+    }
+}
+
+/// コマンドライン引数の設定を反映したインタプリタを作る
+fn new_interpreter(options: &Options) -> Interpreter {
     let mut interpreter = Interpreter::new();
-    let Some(file_path) = args().nth(1) else {
-        panic!("no file path");
-    };
-    run_file(&mut interpreter, &file_path);
-    
+    interpreter.set_iteration_limit(options.max_iterations);
+    interpreter.set_overflow(options.overflow);
+    interpreter.set_precision(options.precision);
+    if options.trace {
+        interpreter.set_tracer(|event| match event {
+            TraceEvent::Statement(statement) => eprintln!("trace: {:?}", statement),
+            TraceEvent::Eval(expr, value) => eprintln!("trace: {:?} => {}", expr, value.debug_display()),
+        });
+    }
+
+    interpreter
 }
 
-fn run(interpreter: &mut Interpreter, code: &str) {
-    let lexer = Lexer::new(code.chars().collect());
+/// 実行に失敗したこと (エラーは表示済み)
+#[derive(Debug)]
+struct Failed;
+
+/// file_pathはcodeを読み込んだファイル (include文の相対パスの基準になる)
+/// 最後の文が式文であればその値を返す (バイトコードで実行したときはNone)
+fn run(interpreter: &mut Interpreter, code: &str, file_path: Option<&str>, options: &Options) -> Result<Option<Primitive>, Failed> {
+    let lexer = Lexer::new(code);
     let mut parser = Parser::new(lexer);
     let program = parser.parse();
 
     // println!("{:?}", program);
 
-    if let Some(program) = program {
-        interpreter.run(&program);
+    if !report_warnings(&parser.warnings(), options) {
+        return Err(Failed);
+    }
+
+    let program = match program {
+        Ok(program) => include::expand(program, file_path.map(Path::new)),
+        Err(error) => {
+            eprintln!("{}", error);
+            return Err(Failed);
+        }
+    };
+
+    match program {
+        Ok(program) => {
+            let program = if options.optimize {
+                optimize::optimize(program)
+            } else {
+                program
+            };
+
+            if options.vm {
+                if let Some(result) = run_vm(&program, options) {
+                    return result.map(|()| None);
+                }
+            }
+
+            let program = resolve::resolve(program);
+
+            match interpreter.run(&program) {
+                Ok(value) => Ok(Some(value)),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    Err(Failed)
+                }
+            }
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            Err(Failed)
+        }
+    }
+}
+
+/// バイトコードにコンパイルして実行する
+/// コンパイルできなければ実行せずにNoneを返す
+fn run_vm(program: &[Statement], options: &Options) -> Option<Result<(), Failed>> {
+    let program = match compile::compile(program) {
+        Ok(program) => program,
+        Err(error) => {
+            if options.warnings {
+                eprintln!("warning: {}", error);
+            }
+            return None;
+        }
+    };
+
+    let mut vm = Vm::new();
+    vm.set_iteration_limit(options.max_iterations);
+    vm.set_overflow(options.overflow);
+    vm.set_precision(options.precision);
+    if let Err(error) = vm.run(&program) {
+        eprintln!("{}", error);
+        return Some(Err(Failed));
+    }
+
+    Some(Ok(()))
+}
+
+/// --evalで与えられたコードを実行する
+/// ファイルと同じくinclude文の展開と名前解決をしてから実行する
+/// 全体が1つの式であればその値をprintと同じ形式で表示し，そうでなければ文の列として実行する
+fn eval_code(interpreter: &mut Interpreter, code: &str, options: &Options) {
+    let is_expression = Parser::new(Lexer::new(code)).parse_single_expr().is_ok();
+    // VMは式の値を返さないため，式はインタプリタで評価する
+    let options = Options {
+        vm: options.vm && !is_expression,
+        ..options.clone()
+    };
+
+    match run(interpreter, code, None, &options) {
+        Ok(Some(value)) if is_expression => println!("{}", interpreter.render(&value)),
+        Ok(_) => (),
+        Err(Failed) => exit(1),
     }
 }
 
-fn run_file(interpreter: &mut Interpreter, file_path: &str) {
-    let mut file = File::open(file_path).unwrap();
+fn read_file(file_path: &str) -> io::Result<String> {
+    let mut file = File::open(file_path)?;
     let mut code = String::new();
-    file.read_to_string(&mut code).unwrap();
+    file.read_to_string(&mut code)?;
 
-    run(interpreter, &code);
+    Ok(code)
 }
 
-///// 対話型
-// fn repl(interpreter: &mut Interpreter) {
-//     loop {
-//         print!(">> ");
-//         io::stdout().flush().unwrap();
+fn run_file(interpreter: &mut Interpreter, file_path: &str, options: &Options) {
+    let code = read_file(file_path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", file_path, error);
+        exit(1);
+    });
 
-//         let mut code = String::new();
-//         io::stdin()
-//             .read_line(&mut code)
-//             .expect("failed to read line");
+    let start = Instant::now();
+    let exit_code = match run(interpreter, &code, Some(file_path), options) {
+        Ok(Some(_)) => call_main(interpreter),
+        Ok(None) => None,
+        Err(Failed) => Some(1),
+    };
+
+    if options.time {
+        eprintln!("time: {:.3} ms", start.elapsed().as_secs_f64() * 1000.0);
+    }
 
-//         if code == "exit\n" {
-//             break;
-//         }
+    if let Some(exit_code) = exit_code {
+        exit(exit_code);
+    }
+}
+
+/// トップレベルの文の後に，main関数が定義されていれば引数なしで呼び出す
+/// mainの返り値を，トップレベルのreturnと同じ規則で終了コードにして返す (nullなら0)
+/// 終了コードにできない値を返したときや実行時エラーのときは，エラーを表示して1を返す
+/// mainが定義されていなければNoneを返す
+fn call_main(interpreter: &mut Interpreter) -> Option<i32> {
+    let Some(Primitive::Function(main)) = interpreter.global("main").cloned() else {
+        return None;
+    };
 
-//         run(interpreter, &code);
-//     }
-// }
\ No newline at end of file
+    let exit_code = match interpreter.call_function(&main, Vec::new()) {
+        Ok(Primitive::Null) => Ok(0),
+        Ok(value) => interpreter::exit_code(&value),
+        Err(error) => Err(error),
+    };
+
+    match exit_code {
+        Ok(exit_code) => Some(exit_code),
+        Err(error) => {
+            eprintln!("{}", error);
+            Some(1)
+        }
+    }
+}
+
+/// 警告を表示し，実行を続けてよいかどうかを返す
+fn report_warnings(warnings: &[Warning], options: &Options) -> bool {
+    if options.warnings || options.strict {
+        for warning in warnings {
+            eprintln!("warning: {}", warning);
+        }
+    }
+
+    !options.strict || warnings.is_empty()
+}
+
+/// 実行せずに構文だけを検査し，終了コードを返す
+fn check_file(file_path: &str, options: &Options) -> i32 {
+    let code = match read_file(file_path) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("{}: {}", file_path, error);
+            return 1;
+        }
+    };
+    let lexer = Lexer::new(&code);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse();
+    if !report_warnings(&parser.warnings(), options) {
+        return 1;
+    }
+
+    let program = match program {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{}:{}", file_path, error);
+            return 1;
+        }
+    };
+
+    match include::expand(program, Some(Path::new(file_path))) {
+        Ok(_) => 0,
+        Err(error) => {
+            eprintln!("{}", error);
+            1
+        }
+    }
+}
+
+/// 実行せずに構文木をJSONで表示し，終了コードを返す
+fn print_ast_json(file_path: &str) -> i32 {
+    let code = match read_file(file_path) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("{}: {}", file_path, error);
+            return 1;
+        }
+    };
+
+    let mut parser = Parser::new(Lexer::new(&code));
+    match parser.parse() {
+        Ok(program) => {
+            println!("{}", ast_json::to_json(&program));
+            0
+        }
+        Err(error) => {
+            eprintln!("{}:{}", file_path, error);
+            1
+        }
+    }
+}
+
+/// REPLで実行前に表示するもの
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dump {
+    Off,
+    /// トークン列
+    Tokens,
+    /// 構文木
+    Ast,
+}
+
+/// 対話型
+fn repl(interpreter: &mut Interpreter, options: &Options, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    let mut dump = Dump::Off;
+    // 直前に評価した結果 (:typeで使う)
+    let mut last = None;
+    let prompt = options.prompt.as_deref().unwrap_or(">> ");
+    loop {
+        if !options.quiet {
+            write!(output, "{}", prompt)?;
+            output.flush()?;
+        }
+
+        let mut code = String::new();
+        if input.read_line(&mut code)? == 0 {
+            break;
+        }
+
+        if code.trim() == "exit" {
+            break;
+        }
+
+        if code.starts_with(':') {
+            run_command(interpreter, options, &mut dump, &mut last, code.trim(), output)?;
+            continue;
+        }
+
+        match dump {
+            Dump::Off => (),
+            Dump::Tokens => dump_tokens(&code, output)?,
+            Dump::Ast => dump_ast(&code, output)?,
+        }
+
+        let Ok(Some(value)) = run(interpreter, &code, None, options) else {
+            continue;
+        };
+
+        match &value {
+            Primitive::Null => {}
+            _ if options.quiet => {}
+            value => writeln!(output, "{}", value.debug_display())?,
+        }
+        last = Some(value);
+    }
+
+    Ok(())
+}
+
+/// REPLのコマンドを実行する
+fn run_command(
+    interpreter: &mut Interpreter,
+    options: &Options,
+    dump: &mut Dump,
+    last: &mut Option<Primitive>,
+    command: &str,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let (command, argument) = command.split_once(' ').unwrap_or((command, ""));
+
+    match command {
+        ":vars" => {
+            for (name, value) in interpreter.globals() {
+                writeln!(output, "{}: {} = {}", name, value.type_name(), value)?;
+            }
+        }
+        ":clear" => {
+            *interpreter = new_interpreter(options);
+            *last = None;
+        }
+        // 直前に評価した結果の型を表示する
+        ":type" => match last {
+            Some(value) => writeln!(output, "{}", value.type_name())?,
+            None => writeln!(output, "no result yet")?,
+        },
+        // 現在の状態を保ったままファイルを実行する
+        ":load" => match read_file(argument.trim()) {
+            Ok(code) => {
+                let _ = run(interpreter, &code, Some(argument.trim()), options);
+            }
+            Err(error) => writeln!(output, "{}: {}", argument.trim(), error)?,
+        },
+        // 以降の入力を実行する前にトークン列や構文木を表示する
+        ":debug" => match argument.trim() {
+            "tokens" => *dump = Dump::Tokens,
+            "ast" => *dump = Dump::Ast,
+            "off" => *dump = Dump::Off,
+            mode => writeln!(output, "unknown debug mode: {} (expected tokens, ast or off)", mode)?,
+        },
+        _ => writeln!(output, "unknown command: {}", command)?,
+    }
+
+    Ok(())
+}
+
+/// codeのトークン列をコメントも含めて表示する
+fn dump_tokens(code: &str, output: &mut impl Write) -> io::Result<()> {
+    let mut lexer = Lexer::new(code);
+    lexer.set_keep_comments(true);
+    while let Some(token) = lexer.token() {
+        writeln!(output, "{:?}", token)?;
+    }
+
+    Ok(())
+}
+
+/// codeの構文木を表示する
+fn dump_ast(code: &str, output: &mut impl Write) -> io::Result<()> {
+    let mut parser = Parser::new(Lexer::new(code));
+    match parser.parse() {
+        Ok(statements) => writeln!(output, "{:#?}", statements),
+        Err(error) => writeln!(output, "{}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// inputを1行ずつREPLに入力し，出力を返す
+    fn repl_output(options: &Options, input: &str) -> String {
+        let mut output = Vec::new();
+        repl(&mut Interpreter::new(), options, &mut input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn vars_lists_globals() {
+        let options = Options { quiet: true, ..Options::default() };
+        let output = repl_output(&options, "y = \"text\"\nx = 1\n:vars\n");
+        assert_eq!(output, "x: number = 1\ny: string = text\n");
+    }
+
+    #[test]
+    fn load_runs_file_in_current_session() {
+        let path = std::env::temp_dir().join(format!("simple-calc-load-{}.calc", std::process::id()));
+        std::fs::write(&path, "loaded = 42\n").unwrap();
+
+        let options = Options { prompt: Some(String::new()), ..Options::default() };
+        let output = repl_output(&options, &format!(":load {}\nloaded + 1\n", path.display()));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(output, "43\n");
+    }
+
+    #[test]
+    fn echo_quotes_strings_but_print_does_not() {
+        let options = Options { prompt: Some(String::new()), ..Options::default() };
+        assert_eq!(repl_output(&options, "\"hello\"\n"), "\"hello\"\n");
+        assert_eq!(repl_output(&options, "[1, \"a\"]\n"), "[1, \"a\"]\n");
+        assert_eq!(repl_output(&options, "42\n"), "42\n");
+    }
+
+    #[test]
+    fn debug_tokens_includes_comments() {
+        let options = Options { prompt: Some(String::new()), ..Options::default() };
+        let output = repl_output(&options, ":debug tokens\nx // note\n");
+        assert_eq!(output, "Identifier(\"x\")\nComment(\" note\")\nNewLine\n0\n");
+    }
+
+    #[test]
+    fn debug_ast_dumps_until_turned_off() {
+        let options = Options { prompt: Some(String::new()), ..Options::default() };
+        let output = repl_output(&options, ":debug ast\n1\n:debug off\n2\n");
+        assert_eq!(output, "[\n    Expr(\n        Number(\n            1.0,\n        ),\n    ),\n]\n1\n2\n");
+    }
+
+    #[test]
+    fn type_shows_type_of_last_result() {
+        let options = Options { prompt: Some(String::new()), quiet: true, ..Options::default() };
+        let output = repl_output(&options, ":type\n1 == 1\n:type\n\"x\"\n:type\nprint 1\n:type\n");
+        assert_eq!(output, "no result yet\nboolean\nstring\nnull\n");
+    }
+}
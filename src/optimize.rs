@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::interpreter::Interpreter;
+use crate::parse::{Expr, Function, ReferenceType, Statement};
+use crate::token::Operator;
+use crate::types::Primitive;
+
+/// 純粋な関数の呼び出しを畳み込むときの，1つのループで繰り返せる回数の上限
+const PURE_CALL_ITERATION_LIMIT: usize = 10000;
+
+/// 文の列に定数畳み込みを行う
+/// トップレベルで定義されたpure関数の，定数だけを引数にとる呼び出しは，その結果に置き換える
+/// ただし，プログラムのどこかで代入や再定義をされる名前の関数は畳み込まない
+pub fn optimize(statements: Vec<Statement>) -> Vec<Statement> {
+    let mut bindings = HashMap::new();
+    count_bindings(&statements, &mut bindings);
+
+    let mut optimizer = Optimizer {
+        pure_functions: HashMap::new(),
+    };
+
+    statements
+        .into_iter()
+        .map(|statement| {
+            let statement = optimizer.optimize_statement(statement);
+            // 定義より前の呼び出しは実行時にエラーになるため，定義した後の呼び出しだけを畳み込む
+            match &statement {
+                Statement::Function { name, function } if function.pure && bindings[name] == 1 => {
+                    optimizer.pure_functions.insert(name.clone(), Rc::clone(function));
+                }
+                Statement::Function { name, .. } => {
+                    optimizer.pure_functions.remove(name);
+                }
+                _ => (),
+            }
+            statement
+        })
+        .collect()
+}
+
+struct Optimizer {
+    /// 呼び出しを畳み込めるpure関数
+    pure_functions: HashMap<String, Rc<Function>>,
+}
+
+impl Optimizer {
+    fn optimize_statements(&mut self, statements: Vec<Statement>) -> Vec<Statement> {
+        statements.into_iter().map(|statement| self.optimize_statement(statement)).collect()
+    }
+
+    /// 文に定数畳み込みを行う
+    fn optimize_statement(&mut self, statement: Statement) -> Statement {
+        match statement {
+            Statement::Return(expr) => Statement::Return(Box::new(self.fold(*expr))),
+            Statement::Print(arguments) => Statement::Print(self.fold_all(arguments)),
+            Statement::Eprint(arguments) => Statement::Eprint(self.fold_all(arguments)),
+            Statement::Expr(expr) => Statement::Expr(Box::new(self.fold(*expr))),
+            Statement::Block(statements) => Statement::Block(self.optimize_statements(statements)),
+            Statement::If { condition, block, else_block } => Statement::If {
+                condition: Box::new(self.fold(*condition)),
+                block: Box::new(self.optimize_statement(*block)),
+                else_block: else_block.map(|block| Box::new(self.optimize_statement(*block))),
+            },
+            Statement::While { condition, block, else_block } => Statement::While {
+                condition: Box::new(self.fold(*condition)),
+                block: Box::new(self.optimize_statement(*block)),
+                else_block: else_block.map(|block| Box::new(self.optimize_statement(*block))),
+            },
+            Statement::WhileLet { name, value, block } => Statement::WhileLet {
+                name,
+                value: Box::new(self.fold(*value)),
+                block: Box::new(self.optimize_statement(*block)),
+            },
+            Statement::Break(value) => Statement::Break(value.map(|value| Box::new(self.fold(*value)))),
+            Statement::Continue | Statement::Include(_) => statement,
+            Statement::Loop(block) => Statement::Loop(Box::new(self.optimize_statement(*block))),
+            Statement::Function { name, function } => Statement::Function {
+                name,
+                function: self.optimize_function(&function),
+            },
+            Statement::TryCatch { try_block, name, catch_block } => Statement::TryCatch {
+                try_block: Box::new(self.optimize_statement(*try_block)),
+                name,
+                catch_block: Box::new(self.optimize_statement(*catch_block)),
+            },
+        }
+    }
+
+    /// 関数の本体に定数畳み込みを行う
+    /// 仮引数と同じ名前のpure関数は，本体の中では呼び出しを畳み込まない
+    fn optimize_function(&mut self, function: &Function) -> Rc<Function> {
+        let shadowed = function
+            .parameters
+            .iter()
+            .filter_map(|parameter| self.pure_functions.remove_entry(parameter))
+            .collect::<Vec<_>>();
+        let body = self.optimize_statements(function.body.clone());
+        self.pure_functions.extend(shadowed);
+
+        Rc::new(Function {
+            name: function.name.clone(),
+            parameters: function.parameters.clone(),
+            body,
+            pure: function.pure,
+        })
+    }
+
+    fn fold_all(&mut self, exprs: Vec<Expr>) -> Vec<Expr> {
+        exprs.into_iter().map(|expr| self.fold(expr)).collect()
+    }
+
+    /// 式に定数畳み込みを行う
+    /// 畳み込みによって実行時の振る舞いが変わる場合(0除算など)は畳み込まない
+    fn fold(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::PrefixExpr { operator, right } => fold_prefix(operator, self.fold(*right)),
+            Expr::InfixExpr { left, operator, right } if is_assign(&operator) => Expr::InfixExpr {
+                left,
+                operator,
+                right: Box::new(self.fold(*right)),
+            },
+            Expr::InfixExpr { left, operator, right } => {
+                let left = self.fold(*left);
+                let right = self.fold(*right);
+                fold_infix(left, operator, right)
+            }
+            Expr::Array(elements) => Expr::Array(self.fold_all(elements)),
+            Expr::Tuple(elements) => Expr::Tuple(self.fold_all(elements)),
+            Expr::Typeof(operand) => Expr::Typeof(Box::new(self.fold(*operand))),
+            Expr::Loop(block) => Expr::Loop(Box::new(self.optimize_statement(*block))),
+            Expr::Function(function) => Expr::Function(self.optimize_function(&function)),
+            Expr::Call { function, arguments } => {
+                let function = self.fold(*function);
+                let arguments = self.fold_all(arguments);
+                self.fold_call(function, arguments)
+            }
+            Expr::Index { left, index } => Expr::Index {
+                left: Box::new(self.fold(*left)),
+                index: Box::new(self.fold(*index)),
+            },
+            Expr::Object(fields) => {
+                Expr::Object(fields.into_iter().map(|(name, value)| (name, self.fold(value))).collect())
+            }
+            Expr::Field { left, name } => Expr::Field {
+                left: Box::new(self.fold(*left)),
+                name,
+            },
+            Expr::Spread(array) => Expr::Spread(Box::new(self.fold(*array))),
+            Expr::Conditional { condition, then_value, else_value } => Expr::Conditional {
+                condition: Box::new(self.fold(*condition)),
+                then_value: Box::new(self.fold(*then_value)),
+                else_value: Box::new(self.fold(*else_value)),
+            },
+            _ => expr,
+        }
+    }
+
+    /// 引数がすべて定数であるpure関数の呼び出しを，その結果に置き換える
+    /// 実行時エラーになる場合や，結果が定数式で表せない場合は畳み込まない
+    fn fold_call(&self, function: Expr, arguments: Vec<Expr>) -> Expr {
+        let folded = match &function {
+            Expr::Identifier(name) => self.call_pure_function(name, &arguments),
+            _ => None,
+        };
+
+        folded.unwrap_or_else(|| Expr::Call {
+            function: Box::new(function),
+            arguments,
+        })
+    }
+
+    fn call_pure_function(&self, name: &str, arguments: &[Expr]) -> Option<Expr> {
+        let function = self.pure_functions.get(name)?;
+        let arguments = arguments.iter().map(literal).collect::<Option<Vec<_>>>()?;
+
+        // pure関数から呼び出せるように，ほかのpure関数も定義しておく
+        let definitions = self
+            .pure_functions
+            .iter()
+            .map(|(name, function)| Statement::Function {
+                name: name.clone(),
+                function: Rc::clone(function),
+            })
+            .collect::<Vec<_>>();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_iteration_limit(Some(PURE_CALL_ITERATION_LIMIT));
+        interpreter.run(&definitions).ok()?;
+
+        match interpreter.call_function(function, arguments).ok()? {
+            value @ (Primitive::Number(_) | Primitive::String(_) | Primitive::Char(_) | Primitive::Boolean(_) | Primitive::Null) => {
+                Some(from_literal(value))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn fold_prefix(operator: Operator, right: Expr) -> Expr {
+    match (&operator, &right) {
+        (Operator::Plus, Expr::Number(n)) => Expr::Number(*n),
+        (Operator::Minus, Expr::Number(n)) => Expr::Number(-n),
+        _ => Expr::PrefixExpr {
+            operator,
+            right: Box::new(right),
+        },
+    }
+}
+
+fn fold_infix(left: Expr, operator: Operator, right: Expr) -> Expr {
+    if let Operator::LogicalAnd | Operator::LogicalOr | Operator::Coalesce = operator {
+        return fold_logical(left, operator, right);
+    }
+
+    let folded = match (literal(&left), literal(&right)) {
+        (Some(l), Some(r)) => fold_literals(&l, &operator, &r),
+        _ => None,
+    };
+
+    folded.unwrap_or_else(|| Expr::InfixExpr {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    })
+}
+
+/// 左辺が定数の論理演算子式，null合体演算子式を畳み込む
+/// 評価されない右辺はもともと評価されないため，取り除いても振る舞いは変わらない
+fn fold_logical(left: Expr, operator: Operator, right: Expr) -> Expr {
+    let Some(l) = literal(&left) else {
+        return Expr::InfixExpr {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        };
+    };
+
+    let take_right = match operator {
+        Operator::LogicalAnd => l.is_truthy(),
+        Operator::LogicalOr => !l.is_truthy(),
+        _ => l == Primitive::Null,
+    };
+
+    if take_right { right } else { left }
+}
+
+/// 定数同士の演算を畳み込む
+fn fold_literals(left: &Primitive, operator: &Operator, right: &Primitive) -> Option<Expr> {
+    let value = match (left, operator, right) {
+        (_, Operator::Div | Operator::Mod, Primitive::Number(r)) if *r == 0.0 => return None,
+        (Primitive::Number(l), _, Primitive::Number(r)) if !is_exact_integer_operation(*l, operator, *r) => return None,
+        (Primitive::Number(_), _, Primitive::Number(_)) => match operator {
+            Operator::Plus => left + right,
+            Operator::Minus => left - right,
+            Operator::Mul => left * right,
+            Operator::Div => left / right,
+            Operator::Mod => left % right,
+            Operator::BitAnd => left & right,
+            Operator::BitOr => left | right,
+            Operator::ShiftLeft => left << right,
+            Operator::ShiftRight => left >> right,
+            _ => return None,
+        },
+        (Primitive::String(_), Operator::Plus, Primitive::String(_)) => left + right,
+        _ => return None,
+    };
+
+    Some(from_literal(value))
+}
+
+/// ビット演算，シフト演算が範囲を超えず，実行時の設定(Overflow)によらず同じ結果になるかどうかを返す
+/// それ以外の演算子では常にtrue
+fn is_exact_integer_operation(left: f64, operator: &Operator, right: f64) -> bool {
+    let is_i32 = |n: f64| n.fract() == 0.0 && (i32::MIN as f64..=i32::MAX as f64).contains(&n);
+    match operator {
+        Operator::BitAnd | Operator::BitOr => is_i32(left) && is_i32(right),
+        Operator::ShiftLeft => {
+            is_i32(left) && (0.0..32.0).contains(&right) && right.fract() == 0.0 && is_i32(left * 2f64.powi(right as i32))
+        }
+        Operator::ShiftRight => is_i32(left) && (0.0..32.0).contains(&right) && right.fract() == 0.0,
+        _ => true,
+    }
+}
+
+/// 定数式であれば値を返す
+fn literal(expr: &Expr) -> Option<Primitive> {
+    match expr {
+        Expr::Number(n) => Some(Primitive::Number(*n)),
+        Expr::String(s) => Some(Primitive::String(s.value.clone())),
+        Expr::Char(c) => Some(Primitive::Char(*c)),
+        Expr::Null => Some(Primitive::Null),
+        Expr::Boolean(b) => Some(Primitive::Boolean(*b)),
+        _ => None,
+    }
+}
+
+fn from_literal(value: Primitive) -> Expr {
+    match value {
+        Primitive::Number(n) => Expr::Number(n),
+        Primitive::String(s) => Expr::String(ReferenceType { value: s }),
+        Primitive::Char(c) => Expr::Char(c),
+        Primitive::Null => Expr::Null,
+        Primitive::Boolean(b) => Expr::Boolean(b),
+        _ => panic!("invalid literal"),
+    }
+}
+
+/// 名前ごとに，関数の定義，代入，while let，catchで束縛される箇所の数を数える
+/// 関数の仮引数は数えない (optimize_functionで扱う)
+fn count_bindings(statements: &[Statement], counts: &mut HashMap<String, usize>) {
+    for statement in statements {
+        match statement {
+            Statement::Return(expr) | Statement::Expr(expr) => count_expr_bindings(expr, counts),
+            Statement::Print(arguments) | Statement::Eprint(arguments) => {
+                arguments.iter().for_each(|argument| count_expr_bindings(argument, counts))
+            }
+            Statement::Block(statements) => count_bindings(statements, counts),
+            Statement::If { condition, block, else_block } | Statement::While { condition, block, else_block } => {
+                count_expr_bindings(condition, counts);
+                count_bindings(std::slice::from_ref(block), counts);
+                if let Some(else_block) = else_block {
+                    count_bindings(std::slice::from_ref(else_block), counts);
+                }
+            }
+            Statement::WhileLet { name, value, block } => {
+                bind(name, counts);
+                count_expr_bindings(value, counts);
+                count_bindings(std::slice::from_ref(block), counts);
+            }
+            Statement::Break(value) => {
+                if let Some(value) = value {
+                    count_expr_bindings(value, counts);
+                }
+            }
+            Statement::Continue | Statement::Include(_) => (),
+            Statement::Loop(block) => count_bindings(std::slice::from_ref(block), counts),
+            Statement::Function { name, function } => {
+                bind(name, counts);
+                count_bindings(&function.body, counts);
+            }
+            Statement::TryCatch { try_block, name, catch_block } => {
+                bind(name, counts);
+                count_bindings(std::slice::from_ref(try_block), counts);
+                count_bindings(std::slice::from_ref(catch_block), counts);
+            }
+        }
+    }
+}
+
+fn count_expr_bindings(expr: &Expr, counts: &mut HashMap<String, usize>) {
+    let count_all = |exprs: &[Expr], counts: &mut HashMap<String, usize>| {
+        exprs.iter().for_each(|expr| count_expr_bindings(expr, counts))
+    };
+
+    match expr {
+        Expr::InfixExpr { left, operator, right } => {
+            if is_assign(operator) {
+                let targets = match left.as_ref() {
+                    Expr::Tuple(targets) => targets.as_slice(),
+                    target => std::slice::from_ref(target),
+                };
+                for target in targets {
+                    if let Expr::Identifier(name) = target {
+                        bind(name, counts);
+                    }
+                }
+            }
+            count_expr_bindings(left, counts);
+            count_expr_bindings(right, counts);
+        }
+        Expr::PrefixExpr { right: operand, .. }
+        | Expr::PostfixExpr { left: operand, .. }
+        | Expr::Typeof(operand)
+        | Expr::Field { left: operand, .. }
+        | Expr::Spread(operand) => count_expr_bindings(operand, counts),
+        Expr::Array(elements) | Expr::Tuple(elements) => count_all(elements, counts),
+        Expr::Loop(block) => count_bindings(std::slice::from_ref(block), counts),
+        Expr::Function(function) => count_bindings(&function.body, counts),
+        Expr::Call { function, arguments } => {
+            count_expr_bindings(function, counts);
+            count_all(arguments, counts);
+        }
+        Expr::Index { left, index } => {
+            count_expr_bindings(left, counts);
+            count_expr_bindings(index, counts);
+        }
+        Expr::Object(fields) => fields.iter().for_each(|(_, value)| count_expr_bindings(value, counts)),
+        Expr::Conditional { condition, then_value, else_value } => {
+            count_expr_bindings(condition, counts);
+            count_expr_bindings(then_value, counts);
+            count_expr_bindings(else_value, counts);
+        }
+        Expr::Identifier(_)
+        | Expr::Local(_)
+        | Expr::Number(_)
+        | Expr::String(_)
+        | Expr::Char(_)
+        | Expr::Null
+        | Expr::Boolean(_) => (),
+    }
+}
+
+fn bind(name: &str, counts: &mut HashMap<String, usize>) {
+    *counts.entry(name.to_string()).or_insert(0) += 1;
+}
+
+fn is_assign(operator: &Operator) -> bool {
+    matches!(
+        operator,
+        Operator::Assign
+            | Operator::AddAssign
+            | Operator::SubAssign
+            | Operator::MulAssign
+            | Operator::DivAssign
+            | Operator::ModAssign
+            | Operator::AndAssign
+            | Operator::OrAssign
+            | Operator::ShiftLeftAssign
+            | Operator::ShiftRightAssign
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_json::to_json;
+    use crate::parse::Parser;
+    use crate::token::Lexer;
+
+    fn parse(code: &str) -> Vec<Statement> {
+        Parser::new(Lexer::new(code)).parse().expect("failed to parse")
+    }
+
+    /// codeを最適化した構文木がexpectedの構文木と等しいことを確かめる
+    fn assert_folds_to(code: &str, expected: &str) {
+        assert_eq!(to_json(&optimize(parse(code))), to_json(&parse(expected)), "{}", code);
+    }
+
+    #[test]
+    fn folds_constant_expressions() {
+        assert_folds_to("x = 1 + 2 * 3", "x = 7");
+        assert_folds_to("print \"a\" + \"b\"", "print \"ab\"");
+        assert_folds_to("x = -(2 - 5)", "x = 3");
+    }
+
+    #[test]
+    fn keeps_side_effects() {
+        assert_folds_to("x = f() + 1 * 2", "x = f() + 2");
+        assert_folds_to("x = 0 && f()", "x = 0");
+        assert_folds_to("x = 1 && f()", "x = f()");
+        assert_folds_to("x = g() && f()", "x = g() && f()");
+        assert_folds_to("x = 1 / 0", "x = 1 / 0");
+    }
+
+    #[test]
+    fn folds_pure_function_calls() {
+        assert_folds_to("pure fn double(x) { return x * 2 }\ny = double(5)", "pure fn double(x) { return x * 2 }\ny = 10");
+        assert_folds_to("fn double(x) { return x * 2 }\ny = double(5)", "fn double(x) { return x * 2 }\ny = double(5)");
+        assert_folds_to("y = double(5)\npure fn double(x) { return x * 2 }", "y = double(5)\npure fn double(x) { return x * 2 }");
+    }
+
+    #[test]
+    fn does_not_fold_rebound_pure_function() {
+        let reassigned = "pure fn d(x) { x * 2 }\ny = d(5)\nd = fn(x) { x * 3 }\nz = d(5)";
+        assert_folds_to(reassigned, reassigned);
+
+        let redefined = "pure fn d(x) { x * 2 }\nif true {\nfn d(x) { x * 3 }\ny = d(5)\n}\nz = d(5)";
+        assert_folds_to(redefined, redefined);
+
+        let destructured = "pure fn d(x) { x * 2 }\nd, e = [1, 2]\ny = d(5)";
+        assert_folds_to(destructured, destructured);
+
+        let caught = "pure fn d(x) { x * 2 }\ntry { error(\"x\") } catch (d) { y = d(5) }";
+        assert_folds_to(caught, caught);
+    }
+}
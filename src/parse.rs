@@ -1,14 +1,24 @@
 use std::rc::Rc;
 
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+use crate::token::LexError;
 use crate::token::Lexer;
+use crate::token::Position;
 use crate::token::Token;
+use crate::token::Warning;
 use crate::token::Reserved;
 use crate::token::Operator;
+use crate::types::TYPE_NAMES;
 
 #[derive(Debug, Clone)]
 pub enum Statement {
     Return(Box<Expr>),
-    Print(Box<Expr>),
+    /// 値を空白で区切って表示する
+    Print(Vec<Expr>),
+    /// 値を空白で区切って標準エラー出力に表示する
+    Eprint(Vec<Expr>),
     Expr(Box<Expr>),
     Block(Vec<Statement>),
     If {
@@ -16,6 +26,69 @@ pub enum Statement {
         block: Box<Statement>,
         else_block: Option<Box<Statement>>,
     },
+    While {
+        condition: Box<Expr>,
+        block: Box<Statement>,
+        else_block: Option<Box<Statement>>,
+    },
+    /// `while let name = value { ... }`
+    /// valueを評価し，nullになるまでその値をnameに束縛してblockを繰り返す
+    WhileLet {
+        name: String,
+        value: Box<Expr>,
+        block: Box<Statement>,
+    },
+    /// breakする値を持つことができる
+    Break(Option<Box<Expr>>),
+    Continue,
+    /// 無限ループ
+    Loop(Box<Statement>),
+    /// 名前つき関数の定義
+    Function {
+        name: String,
+        function: Rc<Function>,
+    },
+    /// ファイルのインクルード (トップレベルのみ，実行前に展開される)
+    Include(String),
+    /// try_blockで発生した実行時エラーをnameに束縛してcatch_blockを実行する
+    TryCatch {
+        try_block: Box<Statement>,
+        name: String,
+        catch_block: Box<Statement>,
+    },
+}
+
+/// 関数
+#[derive(Debug)]
+pub struct Function {
+    /// 関数名 (無名関数ならNone)
+    pub name: Option<String>,
+    /// 仮引数
+    pub parameters: Vec<String>,
+    /// 本体
+    pub body: Vec<Statement>,
+    /// pure修飾子がついているかどうか (定数畳み込みで呼び出しを評価してよい)
+    pub pure: bool,
+}
+
+impl Function {
+    /// エラーメッセージなどに使う名前
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("<anonymous>")
+    }
+}
+
+// 関数は同一のものだけを等しいとする
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for Function {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (self == other).then_some(std::cmp::Ordering::Equal)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -25,16 +98,29 @@ pub struct ReferenceType<T> {
 
 /// 式
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Expr {
     /// 識別子
     Identifier(String),
 
+    /// 添字に解決された関数の仮引数
+    Local(usize),
+
     /// 数字
     Number(f64),
 
     /// 文字列
     String(ReferenceType<String>),
 
+    /// 文字
+    Char(char),
+
+    /// null
+    Null,
+
+    /// 真偽値
+    Boolean(bool),
+
     /// 前置演算子
     PrefixExpr {
         operator: Operator,
@@ -49,10 +135,57 @@ pub enum Expr {
     },
 
     /// 後置演算子
+    #[allow(dead_code)]
     PostfixExpr {
         left: Box<Expr>,
         operator: Operator,
     },
+
+    /// typeof演算子
+    Typeof(Box<Expr>),
+
+    /// 配列リテラル
+    Array(Vec<Expr>),
+
+    /// タプル (複数の値のreturnと分割代入に使う)
+    Tuple(Vec<Expr>),
+
+    /// 式としてのloop (breakした値を持つ)
+    Loop(Box<Statement>),
+
+    /// 関数リテラル
+    Function(Rc<Function>),
+
+    /// 関数呼び出し
+    Call {
+        function: Box<Expr>,
+        arguments: Vec<Expr>,
+    },
+
+    /// 添字アクセス
+    Index {
+        left: Box<Expr>,
+        index: Box<Expr>,
+    },
+
+    /// オブジェクトリテラル (フィールド名と値)
+    Object(Vec<(String, Expr)>),
+
+    /// フィールドアクセス
+    Field {
+        left: Box<Expr>,
+        name: String,
+    },
+
+    /// 配列の要素の展開 (配列リテラルと引数の中だけで使える)
+    Spread(Box<Expr>),
+
+    /// 条件演算子 (condition ? then_value : else_value)
+    Conditional {
+        condition: Box<Expr>,
+        then_value: Box<Expr>,
+        else_value: Box<Expr>,
+    },
 }
 
 impl From<&Token> for Operator {
@@ -66,12 +199,16 @@ impl From<&Token> for Operator {
 }
 
 /// 演算子の優先度
-#[derive(PartialEq, PartialOrd, Debug, Clone)]
+#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
 pub enum Precedence {
     /// 最低
     Lowest,
     /// 代入と複合代入
     Assign,
+    /// 条件演算子 (?:)
+    Conditional,
+    /// ??
+    Coalesce,
     /// ||
     LogicalOr,
     /// &&
@@ -84,6 +221,8 @@ pub enum Precedence {
     Equality,
     /// <, >, <=, >=
     Compare,
+    /// <<, >>
+    Shift,
     /// +, -
     Sum,
     /// *, /
@@ -91,52 +230,204 @@ pub enum Precedence {
     /// 前置演算子
     Prefix,
     ///後置演算子
+    #[allow(dead_code)]
     Postfix,
+    /// 関数呼び出し，添字アクセス，フィールドアクセス
+    Call,
+}
+
+/// 演算子の結合性
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Associativity {
+    /// 左結合 (a - b - c は (a - b) - c)
+    Left,
+    /// 右結合 (a = b = c は a = (b = c))
+    Right,
+}
+
+/// 中置演算子の優先度と結合性の表
+/// パーサは中置演算子についてこの表だけを参照する
+pub const INFIX_OPERATORS: &[(Operator, Precedence, Associativity)] = &[
+    (Operator::Assign, Precedence::Assign, Associativity::Right),
+    (Operator::AddAssign, Precedence::Assign, Associativity::Right),
+    (Operator::SubAssign, Precedence::Assign, Associativity::Right),
+    (Operator::MulAssign, Precedence::Assign, Associativity::Right),
+    (Operator::DivAssign, Precedence::Assign, Associativity::Right),
+    (Operator::ModAssign, Precedence::Assign, Associativity::Right),
+    (Operator::AndAssign, Precedence::Assign, Associativity::Right),
+    (Operator::OrAssign, Precedence::Assign, Associativity::Right),
+    (Operator::ShiftLeftAssign, Precedence::Assign, Associativity::Right),
+    (Operator::ShiftRightAssign, Precedence::Assign, Associativity::Right),
+    (Operator::Coalesce, Precedence::Coalesce, Associativity::Right),
+    (Operator::LogicalOr, Precedence::LogicalOr, Associativity::Left),
+    (Operator::LogicalAnd, Precedence::LogicalAnd, Associativity::Left),
+    (Operator::BitOr, Precedence::BitOr, Associativity::Left),
+    (Operator::BitAnd, Precedence::BitAnd, Associativity::Left),
+    (Operator::Equal, Precedence::Equality, Associativity::Left),
+    (Operator::NotEqual, Precedence::Equality, Associativity::Left),
+    (Operator::ObjectEqual, Precedence::Compare, Associativity::Left),
+    (Operator::GreaterThan, Precedence::Compare, Associativity::Left),
+    (Operator::GreaterThanEqual, Precedence::Compare, Associativity::Left),
+    (Operator::LessThan, Precedence::Compare, Associativity::Left),
+    (Operator::LessThanEqual, Precedence::Compare, Associativity::Left),
+    (Operator::In, Precedence::Compare, Associativity::Left),
+    (Operator::Is, Precedence::Compare, Associativity::Left),
+    (Operator::ShiftLeft, Precedence::Shift, Associativity::Left),
+    (Operator::ShiftRight, Precedence::Shift, Associativity::Left),
+    (Operator::Plus, Precedence::Sum, Associativity::Left),
+    (Operator::Minus, Precedence::Sum, Associativity::Left),
+    (Operator::Mul, Precedence::Product, Associativity::Left),
+    (Operator::Div, Precedence::Product, Associativity::Left),
+    (Operator::Mod, Precedence::Product, Associativity::Left),
+];
+
+/// 中置演算子の優先度と結合性を返す
+/// 中置演算子でなければNone
+pub fn infix_binding(operator: &Operator) -> Option<(Precedence, Associativity)> {
+    INFIX_OPERATORS
+        .iter()
+        .find(|(entry, _, _)| entry == operator)
+        .map(|(_, precedence, associativity)| (*precedence, *associativity))
 }
 
 impl From<&Token> for Precedence {
     /// トークンの優先度を返す
     fn from(value: &Token) -> Self {
-        let Token::Operator(operator) = value else {
-            return Precedence::Lowest;
-        };
+        match value {
+            Token::LParen | Token::LBracket | Token::Dot => Precedence::Call,
+            Token::Question => Precedence::Conditional,
+            Token::Operator(operator) => infix_binding(operator)
+                .map(|(precedence, _)| precedence)
+                .unwrap_or(Precedence::Lowest),
+            _ => Precedence::Lowest,
+        }
+    }
+}
 
-        match operator {
-            Operator::Assign | Operator::AddAssign | Operator::SubAssign | Operator::MulAssign | Operator::DivAssign | Operator::ModAssign => Precedence::Assign,
-            Operator::BitOr => Precedence::BitOr,
-            Operator::BitAnd => Precedence::BitAnd,
-            Operator::LogicalOr => Precedence::LogicalOr,
-            Operator::LogicalAnd => Precedence::LogicalAnd,
-            Operator::Equal | Operator::NotEqual => Precedence::Equality,
-            Operator::GreaterThan | Operator::GreaterThanEqual | Operator::LessThan | Operator::LessThanEqual | Operator::ObjectEqual => Precedence::Compare,
-            Operator::Plus | Operator::Minus => Precedence::Sum,
-            Operator::Div | Operator::Mul | Operator::Mod => Precedence::Product,
-            Operator::Not => Precedence::Prefix,
+/// 構文エラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// 予期しないトークン
+    UnexpectedToken {
+        token: Token,
+        position: Position,
+    },
+    /// 予期しない入力の終わり
+    UnexpectedEof {
+        position: Position,
+    },
+    /// 閉じられていない{
+    UnclosedBrace {
+        position: Position,
+    },
+    /// 閉じられていない(
+    UnclosedParen {
+        position: Position,
+    },
+    /// 対応する(がない)
+    UnmatchedParen {
+        position: Position,
+    },
+    /// 字句解析のエラー
+    Lex {
+        error: LexError,
+        position: Position,
+    },
+    /// is演算子の右辺が型名ではない
+    UnknownType {
+        name: String,
+        position: Position,
+    },
+    /// 代入できない式への代入 (positionは代入演算子の位置)
+    InvalidAssignmentTarget {
+        position: Position,
+    },
+}
 
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedToken { token, position } => {
+                write!(f, "{}: unexpected token {:?}", position, token)
+            }
+            ParseError::UnexpectedEof { position } => {
+                write!(f, "{}: unexpected end of input", position)
+            }
+            ParseError::Lex { error, position } => write!(f, "{}: {}", position, error),
+            ParseError::UnclosedBrace { position } => {
+                write!(f, "{}: unclosed '{{'", position)
+            }
+            ParseError::UnclosedParen { position } => {
+                write!(f, "{}: unclosed '('", position)
+            }
+            ParseError::UnmatchedParen { position } => {
+                write!(f, "{}: unmatched ')'", position)
+            }
+            ParseError::UnknownType { name, position } => {
+                write!(f, "{}: unknown type name {}", position, name)
+            }
+            ParseError::InvalidAssignmentTarget { position } => {
+                write!(f, "{}: invalid assignment target", position)
+            }
         }
     }
 }
 
+/// 代入の左辺にできる式かどうか
+/// 変数，フィールドと，それらを要素とするタプル (分割代入) だけを許す
+fn is_assignment_target(expr: &Expr) -> bool {
+    match expr {
+        Expr::Identifier(_) | Expr::Field { .. } => true,
+        Expr::Tuple(targets) => targets.iter().all(is_assignment_target),
+        _ => false,
+    }
+}
+
+/// ブロックを本体とする文かどうか
+fn ends_with_block(statement: &Statement) -> bool {
+    matches!(
+        statement,
+        Statement::If { .. }
+            | Statement::While { .. }
+            | Statement::WhileLet { .. }
+            | Statement::Loop(_)
+            | Statement::Block(_)
+            | Statement::Function { .. }
+            | Statement::TryCatch { .. }
+    )
+}
+
 /// 構文解析器
 pub struct Parser {
     /// 字句解析器
     lexer: Lexer,
     /// 現在のトークン
     current: Option<Token>,
-    /// 次のトークン
-    peek: Option<Token>,
+    /// 現在のトークンの開始位置
+    current_start: usize,
+    /// 先読みしたトークンとその開始位置
+    /// 常に次のトークンを1つ以上含む
+    lookahead: VecDeque<(Option<Token>, usize)>,
+    /// 文字列リテラルのインターン表
+    /// 同じ内容のリテラルは同じ領域を共有する
+    strings: HashMap<String, Rc<String>>,
+    /// 構文解析中に見つかった警告
+    warnings: Vec<Warning>,
 }
 
 /// 関連関数
 impl Parser {
     pub fn new(mut lexer: Lexer) -> Self {
-        let current = lexer.token();
-        let peek = lexer.token();
+        let (current, current_start) = Self::lex(&mut lexer);
+        let (peek, peek_start) = Self::lex(&mut lexer);
 
         Parser {
             lexer,
             current,
-            peek,
+            current_start,
+            lookahead: VecDeque::from([(peek, peek_start)]),
+            strings: HashMap::new(),
+            warnings: Vec::new(),
         }
     }
 }
@@ -144,16 +435,70 @@ impl Parser {
 /// インスタンスメソッド
 impl Parser {
     pub fn next(&mut self) {
-        self.current = self.peek.clone();
-        self.peek = self.lexer.token();
+        (self.current, self.current_start) = self.lookahead.pop_front().unwrap_or((None, 0));
+        if self.lookahead.is_empty() {
+            self.read_token();
+        }
+    }
+
+    /// 字句解析器からトークンを1つ読み，先読みの末尾に加える
+    fn read_token(&mut self) {
+        let token = Self::lex(&mut self.lexer);
+        self.lookahead.push_back(token);
+    }
+
+    /// 字句解析器からコメント以外のトークンとその開始位置を読む
+    fn lex(lexer: &mut Lexer) -> (Option<Token>, usize) {
+        loop {
+            let token = lexer.token();
+            if !matches!(token, Some(Token::Comment(_))) {
+                return (token, lexer.token_start());
+            }
+        }
+    }
+
+    /// 次のトークン
+    fn peek(&self) -> Option<&Token> {
+        self.lookahead[0].0.as_ref()
+    }
+
+    /// n個先のトークンを返す (peek_n(0)は現在のトークン，peek_n(1)は次のトークン)
+    pub fn peek_n(&mut self, n: usize) -> Option<&Token> {
+        if n == 0 {
+            return self.current.as_ref();
+        }
+
+        while self.lookahead.len() < n {
+            self.read_token();
+        }
+        self.lookahead[n - 1].0.as_ref()
+    }
+
+    /// 解析中に見つかった警告
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.lexer.warnings().iter().chain(&self.warnings).cloned().collect()
     }
 
     /// 解析を開始する
-    pub fn parse(&mut self) -> Option<Vec<Statement>> {
+    pub fn parse(&mut self) -> Result<Vec<Statement>, ParseError> {
         let mut statements = Vec::new();
         
         while self.current.is_some() {
-            let statement = self.parse_statement()?;
+            if self.current == Some(Token::NewLine) {
+                self.next();
+                continue;
+            }
+
+            let statement = match self.current {
+                Some(Token::Reserved(Reserved::Include)) => self.parse_include_statement()?,
+                _ => self.parse_statement()?,
+            };
+
+            // parse_blockと同じく，}で終わる文の後だけは同じ行に文を続けられる
+            let ends_with_brace = self.current == Some(Token::RBrace) && ends_with_block(&statement);
+            if !ends_with_brace && !self.peeking_statement_end() {
+                return Err(self.unexpected_statement_end());
+            }
             statements.push(*statement);
 
             self.skip_newline_eof();
@@ -161,7 +506,26 @@ impl Parser {
             self.next();
         }
 
-        Some(statements)
+        Ok(statements)
+    }
+
+    /// 入力全体を1つの式として解析する
+    /// 式の前後の改行は無視する
+    pub fn parse_single_expr(&mut self) -> Result<Box<Expr>, ParseError> {
+        while self.current == Some(Token::NewLine) {
+            self.next();
+        }
+
+        let expr = self.parse_expr(Precedence::Lowest)?;
+        while self.is_peek(&Token::NewLine) {
+            self.next();
+        }
+
+        if self.peeking_eof() {
+            Ok(expr)
+        } else {
+            Err(self.unexpected_statement_end())
+        }
     }
 
     fn skip_newline_eof(&mut self) {
@@ -171,175 +535,543 @@ impl Parser {
         }
     }
 
-    pub fn parse_statement(&mut self) -> Option<Box<Statement>> {
-        match self.current.as_ref()? {
-            Token::Reserved(Reserved::Print) => self.parse_print_statement(),
+    pub fn parse_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        let is_function_statement = self.current == Some(Token::Reserved(Reserved::Fn))
+            && matches!(self.peek_n(1), Some(Token::Identifier(_)))
+            && self.peek_n(2) == Some(&Token::LParen);
+        // `{ key: ...` はブロックではなくオブジェクトリテラル
+        let is_object_literal = self.current == Some(Token::LBrace)
+            && matches!(self.peek_n(1), Some(Token::Identifier(_) | Token::String(_)))
+            && self.peek_n(2) == Some(&Token::Colon);
+
+        match self.current()? {
+            Token::Reserved(Reserved::Print | Reserved::Eprint) => self.parse_print_statement(),
             Token::Reserved(Reserved::Return) => self.parse_return_statement(),
             Token::Reserved(Reserved::If) => self.parse_if_statement(),
-            _ => self.parse_expr(Precedence::Lowest).map(|expr| Box::new(Statement::Expr(expr))),
+            Token::Reserved(Reserved::While) => self.parse_while_statement(),
+            Token::Reserved(Reserved::Break) => self.parse_break_statement(),
+            Token::Reserved(Reserved::Loop) => self.parse_loop().map(Statement::Loop).map(Box::new),
+            Token::Reserved(Reserved::Continue) => Ok(Box::new(Statement::Continue)),
+            Token::Reserved(Reserved::Try) => self.parse_try_statement(),
+            Token::LBrace if is_object_literal => self.parse_expr_statement(),
+            Token::LBrace => self.parse_block(),
+            Token::Reserved(Reserved::Fn) if is_function_statement => {
+                self.parse_function_statement()
+            }
+            Token::Reserved(Reserved::Pure) => self.parse_function_statement(),
+            _ => self.parse_expr_statement(),
         }
     }
 
-    fn parse_block(&mut self) -> Option<Box<Statement>> {
-        if self.current.as_ref()? != &Token::LBrace { return None; }
+    /// 式文を解析する
+    /// `x, y = expr` の形であれば分割代入として解析する
+    fn parse_expr_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        let first = self.parse_expr(Precedence::Assign)?;
+        if !self.is_peek(&Token::Comma) {
+            let expr = self.parse_infix_loop(first, Precedence::Lowest, Associativity::Left)?;
+            return Ok(Box::new(Statement::Expr(expr)));
+        }
+
+        let mut targets = vec![*first];
+        while self.is_peek(&Token::Comma) {
+            self.next();
+            self.next();
+            targets.push(*self.parse_expr(Precedence::Assign)?);
+        }
 
-        self.skip_newline_eof();
+        self.expect_peek(&Token::Operator(Operator::Assign))?;
+        if !targets.iter().all(is_assignment_target) {
+            return Err(ParseError::InvalidAssignmentTarget {
+                position: self.lexer.location(self.current_start),
+            });
+        }
         self.next();
+        let value = self.parse_tuple_expr()?;
+
+        Ok(Box::new(Statement::Expr(Box::new(Expr::InfixExpr {
+            left: Box::new(Expr::Tuple(targets)),
+            operator: Operator::Assign,
+            right: value,
+        }))))
+    }
+
+    /// カンマで区切られた式を解析する
+    /// 2つ以上あればタプルにする
+    fn parse_tuple_expr(&mut self) -> Result<Box<Expr>, ParseError> {
+        let first = self.parse_expr(Precedence::Lowest)?;
+        if !self.is_peek(&Token::Comma) {
+            return Ok(first);
+        }
+
+        let mut elements = vec![*first];
+        while self.is_peek(&Token::Comma) {
+            self.next();
+            self.next();
+            elements.push(*self.parse_expr(Precedence::Lowest)?);
+        }
+
+        Ok(Box::new(Expr::Tuple(elements)))
+    }
+
+    fn parse_block(&mut self) -> Result<Box<Statement>, ParseError> {
+        if self.current()? != &Token::LBrace { return Err(self.unexpected_current()); }
+        let open = self.lexer.location(self.current_start);
 
         let mut statements = Vec::new();
-        while *self.current.as_ref()? != Token::RBrace && !self.is_peek(&Token::RBrace) && !self.peeking_eof() {
+        loop {
+            self.next();
+            match self.current {
+                None => return Err(ParseError::UnclosedBrace { position: open }),
+                Some(Token::NewLine) => continue,
+                Some(Token::RBrace) => break,
+                Some(_) => (),
+            }
+
             let statement = self.parse_statement()?;
 
+            // `if x { break } i = i + 1` のように，}で終わる文の後には同じ行に文を続けられる
+            let ends_with_brace = self.current == Some(Token::RBrace) && ends_with_block(&statement);
+            if !ends_with_brace && !self.peeking_statement_end() {
+                return Err(self.unexpected_statement_end());
+            }
             statements.push(*statement);
+        }
 
-            self.skip_newline_eof();
-            self.next();
+        Ok(Box::new(Statement::Block(statements)))
+    }
+
+    /// if文，while文の本体を解析する
+    /// {}で囲まれていなければ1つの文を本体とする
+    fn parse_body(&mut self) -> Result<Box<Statement>, ParseError> {
+        if self.current()? == &Token::LBrace {
+            return self.parse_block();
         }
 
-        Some(Box::new(Statement::Block(statements)))
+        let statement = self.parse_statement()?;
+        Ok(Box::new(Statement::Block(vec![*statement])))
     }
 
     /// 式を解析する
-    pub fn parse_expr(&mut self, precedence: Precedence) -> Option<Box<Expr>> {
-        let mut left = self.parse_prefix()?;
+    pub fn parse_expr(&mut self, precedence: Precedence) -> Result<Box<Expr>, ParseError> {
+        self.parse_expr_bound(precedence, Associativity::Left)
+    }
+
+    /// 右結合の演算子の右辺を解析するときは，同じ優先度の演算子も右辺に含める
+    fn parse_expr_bound(&mut self, precedence: Precedence, associativity: Associativity) -> Result<Box<Expr>, ParseError> {
+        let left = self.parse_prefix()?;
+        self.parse_infix_loop(left, precedence, associativity)
+    }
+
+    /// 解析済みの左辺に続く中置演算子，後置演算子を解析する
+    fn parse_infix_loop(&mut self, mut left: Box<Expr>, precedence: Precedence, associativity: Associativity) -> Result<Box<Expr>, ParseError> {
+        loop {
+            let peeking = self.peeking_precedence();
+            let binds = match associativity {
+                Associativity::Left => precedence < peeking,
+                Associativity::Right => precedence <= peeking,
+            };
+            if !binds {
+                break;
+            }
 
-        while precedence < self.peeking_precedence() {
             self.next();
-            left = self.parse_postfix(left.clone())
-                .or_else(|| self.parse_infix(left))?;
+            left = match self.parse_postfix(&left) {
+                Some(expr) => expr,
+                None => self.parse_infix(left)?,
+            };
         }
 
         // println!("{:?}", left);
 
-        Some(left)
+        Ok(left)
     }
 
-    fn parse_print_statement(&mut self) -> Option<Box<Statement>> {
-        if self.current.as_ref()? != &Token::Reserved(Reserved::Print) { return None; }
+    /// print文，eprint文を解析する
+    fn parse_print_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        let to_stderr = match self.current()? {
+            Token::Reserved(Reserved::Print) => false,
+            Token::Reserved(Reserved::Eprint) => true,
+            _ => return Err(self.unexpected_current()),
+        };
         self.next();
 
-        let expression = self.parse_expr(Precedence::Lowest);
+        // print(a, b) は print a, b と同じ
+        let mut arguments = if self.current()? == &Token::LParen {
+            let mut arguments = self.parse_expr_list(&Token::RParen)?;
+            if arguments.len() == 1 && !self.peeking_statement_end() {
+                // print (1 + 2) * 3 のように括弧で始まる式
+                let first = Box::new(arguments.remove(0));
+                arguments = vec![*self.parse_infix_loop(first, Precedence::Lowest, Associativity::Left)?];
+            }
+            arguments
+        } else {
+            vec![*self.parse_expr(Precedence::Lowest)?]
+        };
+
+        while self.is_peek(&Token::Comma) {
+            self.next();
+            self.next();
+            arguments.push(*self.parse_expr(Precedence::Lowest)?);
+        }
 
-        if self.is_peek(&Token::NewLine) || self.peeking_eof() || self.is_peek(&Token::RBrace) {
-            expression.map(|expr| Box::new(Statement::Print(expr)))
+        if !self.peeking_statement_end() {
+            Err(self.unexpected_statement_end())
+        } else if to_stderr {
+            Ok(Box::new(Statement::Eprint(arguments)))
         } else {
-            None
+            Ok(Box::new(Statement::Print(arguments)))
         }
     }
 
-    fn parse_return_statement(&mut self) -> Option<Box<Statement>> {
-        if self.current.as_ref()? != &Token::Reserved(Reserved::Return) { return None; }
+    fn parse_return_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        if self.current()? != &Token::Reserved(Reserved::Return) { return Err(self.unexpected_current()); }
 
         self.next();
-        let expression = self.parse_expr(Precedence::Lowest);
+        let expression = self.parse_tuple_expr()?;
 
-        if self.is_peek(&Token::NewLine) || self.peeking_eof() {
-            expression.map(|expr| Box::new(Statement::Return(expr)))
+        if self.peeking_statement_end() {
+            Ok(Box::new(Statement::Return(expression)))
         } else {
-            None
+            Err(self.unexpected_statement_end())
         }
     }
 
-    fn parse_if_statement(&mut self) -> Option<Box<Statement>> {
-        if self.current.as_ref()? != &Token::Reserved(Reserved::If) { return None; }
+    /// if文を解析する
+    /// `elif` は `else if` と同じように，else_blockの中のif文として解析する
+    fn parse_if_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        if !matches!(self.current()?, Token::Reserved(Reserved::If | Reserved::Elif)) { return Err(self.unexpected_current()); }
 
         self.next();
 
-        let condition = self.parse_expr(Precedence::Lowest);
+        let condition = self.parse_condition()?;
 
         self.next();
 
-        let block = self.parse_block()?;
+        let block = self.parse_body()?;
 
         let mut else_block: Option<Box<Statement>> = None;
 
         if self.is_peek(&Token::Reserved(Reserved::Else)) {
             self.next();
             self.next();
-            else_block = self.parse_block();
+            else_block = Some(self.parse_body()?);
+        } else if self.is_peek(&Token::Reserved(Reserved::Elif)) {
+            self.next();
+            else_block = Some(Box::new(Statement::Block(vec![*self.parse_if_statement()?])));
         }
 
-        Some(Box::new(Statement::If {
-            condition: condition?,
+        Ok(Box::new(Statement::If {
+            condition,
             block,
             else_block,
         }))
     }
 
+    fn parse_while_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        if self.current()? != &Token::Reserved(Reserved::While) { return Err(self.unexpected_current()); }
+
+        if self.is_peek(&Token::Reserved(Reserved::Let)) {
+            self.next();
+            return self.parse_while_let_statement();
+        }
+
+        self.next();
+
+        let condition = self.parse_condition()?;
+
+        self.next();
+
+        let block = self.parse_body()?;
+
+        let mut else_block: Option<Box<Statement>> = None;
+
+        if self.is_peek(&Token::Reserved(Reserved::Else)) {
+            self.next();
+            self.next();
+            else_block = Some(self.parse_body()?);
+        }
+
+        Ok(Box::new(Statement::While {
+            condition,
+            block,
+            else_block,
+        }))
+    }
+
+    /// while let文を解析する (現在のトークンはlet)
+    fn parse_while_let_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        if self.current()? != &Token::Reserved(Reserved::Let) { return Err(self.unexpected_current()); }
+
+        self.next();
+        let Some(Token::Identifier(name)) = self.current.clone() else {
+            return Err(self.unexpected_current());
+        };
+        self.expect_peek(&Token::Operator(Operator::Assign))?;
+        self.next();
+        let value = self.parse_expr(Precedence::Lowest)?;
+
+        self.next();
+        let block = self.parse_body()?;
+
+        Ok(Box::new(Statement::WhileLet { name, value, block }))
+    }
+
+    /// if文，while文の条件式を解析する
+    /// 括弧で囲まれていない代入は==の書き間違いとして警告する
+    fn parse_condition(&mut self) -> Result<Box<Expr>, ParseError> {
+        let position = self.lexer.location(self.current_start);
+        let opened = self.current()? == &Token::LParen;
+
+        let condition = self.parse_expr(Precedence::Lowest)?;
+
+        let parenthesized = opened && self.current == Some(Token::RParen);
+        if let Expr::InfixExpr { operator, .. } = condition.as_ref() {
+            let is_assign = matches!(infix_binding(operator), Some((Precedence::Assign, _)));
+            if is_assign && !parenthesized {
+                self.warnings.push(Warning::AssignmentInCondition { position });
+            }
+        }
+
+        Ok(condition)
+    }
+
+    /// include文を解析する
+    fn parse_include_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        if self.current()? != &Token::Reserved(Reserved::Include) { return Err(self.unexpected_current()); }
+        self.next();
+
+        let Some(Token::String(path)) = &self.current else {
+            return Err(self.unexpected_current());
+        };
+
+        Ok(Box::new(Statement::Include(path.clone())))
+    }
+
+    /// break文を解析する
+    fn parse_break_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        if self.current()? != &Token::Reserved(Reserved::Break) { return Err(self.unexpected_current()); }
+
+        if self.peeking_statement_end() {
+            return Ok(Box::new(Statement::Break(None)));
+        }
+
+        self.next();
+        let value = self.parse_expr(Precedence::Lowest)?;
+
+        Ok(Box::new(Statement::Break(Some(value))))
+    }
+
+    /// loopの本体を解析する
+    fn parse_loop(&mut self) -> Result<Box<Statement>, ParseError> {
+        if self.current()? != &Token::Reserved(Reserved::Loop) { return Err(self.unexpected_current()); }
+
+        self.expect_peek(&Token::LBrace)?;
+
+        self.parse_block()
+    }
+
+    /// try文を解析する
+    fn parse_try_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        if self.current()? != &Token::Reserved(Reserved::Try) { return Err(self.unexpected_current()); }
+
+        self.expect_peek(&Token::LBrace)?;
+        let try_block = self.parse_block()?;
+
+        self.expect_peek(&Token::Reserved(Reserved::Catch))?;
+        self.expect_peek(&Token::LParen)?;
+        self.next();
+        let Some(Token::Identifier(name)) = self.current.clone() else {
+            return Err(self.unexpected_current());
+        };
+        self.expect_peek(&Token::RParen)?;
+
+        self.expect_peek(&Token::LBrace)?;
+        let catch_block = self.parse_block()?;
+
+        Ok(Box::new(Statement::TryCatch { try_block, name, catch_block }))
+    }
+
+    /// 名前つき関数の定義を解析する
+    /// `pure fn name(...)` であれば副作用のない関数とする
+    fn parse_function_statement(&mut self) -> Result<Box<Statement>, ParseError> {
+        let pure = self.current()? == &Token::Reserved(Reserved::Pure);
+        if pure {
+            self.expect_peek(&Token::Reserved(Reserved::Fn))?;
+        }
+
+        if self.current()? != &Token::Reserved(Reserved::Fn) { return Err(self.unexpected_current()); }
+        self.next();
+
+        let Some(Token::Identifier(name)) = self.current.clone() else {
+            return Err(self.unexpected_current());
+        };
+
+        let function = self.parse_function(Some(name.clone()), pure)?;
+
+        Ok(Box::new(Statement::Function { name, function }))
+    }
+
     /// 前置演算子式，識別子，数字を解析する
-    pub fn parse_prefix(&mut self) -> Option<Box<Expr>> {
-        match self.current.as_ref()? {
+    pub fn parse_prefix(&mut self) -> Result<Box<Expr>, ParseError> {
+        match self.current()? {
             Token::Operator(Operator::Plus) | Token::Operator(Operator::Minus) | Token::Operator(Operator::Not) => self.parse_prefix_expr(),
             Token::Identifier(name) => {
-                Some(Box::new(Expr::Identifier(name.clone())))
+                Ok(Box::new(Expr::Identifier(name.clone())))
             }
             Token::Number(_) => self.parse_number(),
             Token::String(_) => self.parse_string(),
+            Token::Char(c) => Ok(Box::new(Expr::Char(*c))),
             Token::LParen => self.parse_grouped_expr(),
-            _ => None,
+            Token::LBracket => self.parse_array(),
+            Token::LBrace => self.parse_object(),
+            Token::Reserved(Reserved::Null) => Ok(Box::new(Expr::Null)),
+            Token::Reserved(Reserved::True) => Ok(Box::new(Expr::Boolean(true))),
+            Token::Reserved(Reserved::False) => Ok(Box::new(Expr::Boolean(false))),
+            Token::Reserved(Reserved::Typeof) => self.parse_typeof_expr(),
+            Token::Reserved(Reserved::Fn) => self.parse_function_literal(),
+            Token::Reserved(Reserved::Loop) => self.parse_loop().map(Expr::Loop).map(Box::new),
+            _ => Err(self.unexpected_current()),
         }
     }
 
     /// 前置演算子式を解析する
-    pub fn parse_prefix_expr(&mut self) -> Option<Box<Expr>> {
-        match self.current.as_ref()? {
-            Token::Operator(_) => (),
-            _ => return None,
+    /// `- + -3` のような連続した前置演算子は，右から順に入れ子にする
+    pub fn parse_prefix_expr(&mut self) -> Result<Box<Expr>, ParseError> {
+        let operator = match self.current()? {
+            Token::Operator(operator @ (Operator::Plus | Operator::Minus | Operator::Not)) => operator.clone(),
+            _ => return Err(self.unexpected_current()),
         };
+        self.next();
 
-        let operator = Operator::from(self.current.as_ref()?);
+        let right = self.parse_expr(Precedence::Prefix)?;
+
+        Ok(Box::new(Expr::PrefixExpr { operator, right }))
+    }
+
+    /// typeof式を解析する
+    fn parse_typeof_expr(&mut self) -> Result<Box<Expr>, ParseError> {
+        if self.current()? != &Token::Reserved(Reserved::Typeof) { return Err(self.unexpected_current()); }
         self.next();
 
-        let number = self.parse_expr(Precedence::Prefix);
+        let operand = self.parse_expr(Precedence::Prefix)?;
 
-        match operator {
-            Operator::Plus | Operator::Minus | Operator::Not => Some(Box::new(Expr::PrefixExpr {
-                operator,
-                right: number?,
-            })),
-            _ => None,
-        }
+        Ok(Box::new(Expr::Typeof(operand)))
     }
 
     /// 数字を解析する
-    pub fn parse_number(&mut self) -> Option<Box<Expr>> {
+    pub fn parse_number(&mut self) -> Result<Box<Expr>, ParseError> {
         if let Some(Token::Number(n)) = self.current {
-             Some(Box::new(Expr::Number(n)))
+             Ok(Box::new(Expr::Number(n)))
         } else {
-            None
+            Err(self.unexpected_current())
         }
     }
 
     /// 文字列を解析する
-    pub fn parse_string(&mut self) -> Option<Box<Expr>> {
-        if let Some(Token::String(s)) = self.current.as_ref() {
-             Some(Box::new(Expr::String(
-                    ReferenceType {
-                        value: s.clone().into(),
-                    }
-             )))
+    pub fn parse_string(&mut self) -> Result<Box<Expr>, ParseError> {
+        if let Some(Token::String(s)) = self.current.clone() {
+             Ok(Box::new(self.intern(&s)))
         } else {
-            None
+            Err(self.unexpected_current())
         }
     }
 
+    /// 文字列リテラルの式を作る
+    /// 同じ内容のリテラルは同じ領域を共有する
+    fn intern(&mut self, s: &str) -> Expr {
+        let value = self.strings
+            .entry(s.to_string())
+            .or_insert_with(|| Rc::new(s.to_string()))
+            .clone();
+
+        Expr::String(ReferenceType { value })
+    }
+
     /// 括弧で囲まれた式を解析する
-    pub fn parse_grouped_expr(&mut self) -> Option<Box<Expr>> {
+    pub fn parse_grouped_expr(&mut self) -> Result<Box<Expr>, ParseError> {
+        let open = self.lexer.location(self.current_start);
         self.next();
-        let expression = self.parse_expr(Precedence::Lowest);
+        let expression = self.parse_expr(Precedence::Lowest)?;
+
+        self.expect_close_paren(open)?;
+
+        Ok(expression)
+    }
+
+    /// 配列リテラルを解析する
+    pub fn parse_array(&mut self) -> Result<Box<Expr>, ParseError> {
+        let elements = self.parse_expr_list(&Token::RBracket)?;
+
+        Ok(Box::new(Expr::Array(elements)))
+    }
+
+    /// オブジェクトリテラルを解析する
+    fn parse_object(&mut self) -> Result<Box<Expr>, ParseError> {
+        let mut fields = Vec::new();
+        while !self.is_peek(&Token::RBrace) {
+            if !fields.is_empty() {
+                self.expect_peek(&Token::Comma)?;
+            }
 
-        if self.is_peek(&Token::RParen) {
             self.next();
-            expression
-        } else {
-            None
+            let name = match self.current.clone() {
+                Some(Token::Identifier(name) | Token::String(name)) => name,
+                _ => return Err(self.unexpected_current()),
+            };
+            self.expect_peek(&Token::Colon)?;
+            self.next();
+            fields.push((name, *self.parse_expr(Precedence::Lowest)?));
         }
+        self.next();
+
+        Ok(Box::new(Expr::Object(fields)))
+    }
+
+    /// 関数リテラルを解析する
+    pub fn parse_function_literal(&mut self) -> Result<Box<Expr>, ParseError> {
+        let function = self.parse_function(None, false)?;
+
+        Ok(Box::new(Expr::Function(function)))
+    }
+
+    /// 仮引数と本体を解析する
+    /// currentは(の直前のトークンを指している
+    fn parse_function(&mut self, name: Option<String>, pure: bool) -> Result<Rc<Function>, ParseError> {
+        self.expect_peek(&Token::LParen)?;
+
+        let mut parameters = Vec::new();
+        while !self.is_peek(&Token::RParen) {
+            if !parameters.is_empty() {
+                self.expect_peek(&Token::Comma)?;
+            }
+
+            self.next();
+            let Some(Token::Identifier(parameter)) = self.current.clone() else {
+                return Err(self.unexpected_current());
+            };
+            parameters.push(parameter);
+        }
+        self.next();
+        self.next();
+
+        let Statement::Block(body) = *self.parse_block()? else {
+            unreachable!()
+        };
+
+        Ok(Rc::new(Function {
+            name,
+            parameters,
+            body,
+            pure,
+        }))
     }
 
     /// 後置演算子式を解析する
-    pub fn parse_postfix(&mut self, _left: Box<Expr>) -> Option<Box<Expr>> {
-        let token = self.current.as_ref()?;
-        let _operator = Operator::from(token);
+    /// 後置演算子でなければNoneを返す
+    pub fn parse_postfix(&mut self, _left: &Expr) -> Option<Box<Expr>> {
+        let Token::Operator(_operator) = self.current.as_ref()? else {
+            return None;
+        };
 
         // ここに追加していく
         
@@ -350,60 +1082,411 @@ impl Parser {
     }
 
     /// 中置演算子式の場合に式を解析する
-    pub fn parse_infix(&mut self, left: Box<Expr>) -> Option<Box<Expr>> {
-        let token = self.current.as_ref()?;
-        let Token::Operator(operator) = token else {
-            return Some(left);
-        };
+    pub fn parse_infix(&mut self, left: Box<Expr>) -> Result<Box<Expr>, ParseError> {
+        let token = self.current()?;
+        match token {
+            Token::LParen => return self.parse_call_expr(left),
+            Token::LBracket => return self.parse_index_expr(left),
+            Token::Dot => return self.parse_field_expr(left),
+            Token::Question => return self.parse_conditional_expr(left),
+            _ => (),
+        }
 
-        match operator {
-            Operator::Plus | Operator::Minus | Operator::Mul | Operator::Div | Operator::Mod
-            | Operator::Equal | Operator::NotEqual
-            | Operator::GreaterThan | Operator::GreaterThanEqual | Operator::LessThan | Operator::LessThanEqual | Operator::ObjectEqual
-            | Operator::LogicalAnd | Operator::LogicalOr
-            | Operator::Assign | Operator::AddAssign | Operator::SubAssign | Operator::MulAssign | Operator::DivAssign | Operator::ModAssign
-            | Operator::BitAnd | Operator::BitOr => self.parse_infix_expr(left),
-            _ => Some(left),
+        match token {
+            Token::Operator(operator) if infix_binding(operator).is_some() => self.parse_infix_expr(left),
+            _ => Ok(left),
         }
     }
 
     /// 中置演算子式を解析する
-    pub fn parse_infix_expr(&mut self, left: Box<Expr>) -> Option<Box<Expr>> {
-        let operator = Operator::from(self.current.as_ref()?);
-        let precedence = Precedence::from(self.current.as_ref()?);
+    pub fn parse_infix_expr(&mut self, left: Box<Expr>) -> Result<Box<Expr>, ParseError> {
+        let operator = Operator::from(self.current()?);
+        let Some((precedence, associativity)) = infix_binding(&operator) else {
+            return Err(self.unexpected_current());
+        };
+        if precedence == Precedence::Assign && !is_assignment_target(&left) {
+            return Err(ParseError::InvalidAssignmentTarget {
+                position: self.lexer.location(self.current_start),
+            });
+        }
 
         self.next();
 
-        let right = self.parse_expr(precedence)?;
+        if operator == Operator::Is {
+            return self.parse_is_expr(left);
+        }
 
-        Some(Box::new(Expr::InfixExpr {
+        let right = self.parse_expr_bound(precedence, associativity)?;
+
+        Ok(Box::new(Expr::InfixExpr {
             left,
             operator,
             right,
         }))
     }
 
-    /// 次のトークンの優先度を返す
-    pub fn peeking_precedence(&self) -> Precedence {
-        let token = &self.peek;
+    /// `x is number` を解析し，`typeof x == "number"` にする
+    /// currentは型名を指している
+    fn parse_is_expr(&mut self, left: Box<Expr>) -> Result<Box<Expr>, ParseError> {
+        let name = match self.current()? {
+            Token::Identifier(name) => name.clone(),
+            // nullは予約語として読み込まれる
+            Token::Reserved(Reserved::Null) => "null".to_string(),
+            _ => return Err(self.unexpected_current()),
+        };
+        if !TYPE_NAMES.contains(&name.as_str()) {
+            return Err(ParseError::UnknownType {
+                name,
+                position: self.lexer.location(self.current_start),
+            });
+        }
+
+        Ok(Box::new(Expr::InfixExpr {
+            left: Box::new(Expr::Typeof(left)),
+            operator: Operator::Equal,
+            right: Box::new(self.intern(&name)),
+        }))
+    }
+
+    /// 条件演算子式を解析する
+    /// 代入より強く結合するため `x = a ? b : c` は `x = (a ? b : c)` になる
+    /// elseの値は右結合で代入も含むため，`a ? b : c ? d : e` は `a ? b : (c ? d : e)`，
+    /// `a ? b : c = d` は `a ? b : (c = d)` になる
+    fn parse_conditional_expr(&mut self, condition: Box<Expr>) -> Result<Box<Expr>, ParseError> {
+        self.next();
+        let then_value = self.parse_expr(Precedence::Lowest)?;
+
+        self.expect_peek(&Token::Colon)?;
+        self.next();
+        let else_value = self.parse_expr_bound(Precedence::Assign, Associativity::Right)?;
+
+        Ok(Box::new(Expr::Conditional {
+            condition,
+            then_value,
+            else_value,
+        }))
+    }
+
+    /// 関数呼び出し式を解析する
+    pub fn parse_call_expr(&mut self, function: Box<Expr>) -> Result<Box<Expr>, ParseError> {
+        let arguments = self.parse_expr_list(&Token::RParen)?;
+
+        Ok(Box::new(Expr::Call {
+            function,
+            arguments,
+        }))
+    }
+
+    /// 添字アクセス式を解析する
+    pub fn parse_index_expr(&mut self, left: Box<Expr>) -> Result<Box<Expr>, ParseError> {
+        self.next();
+        let index = self.parse_expr(Precedence::Lowest)?;
+
+        self.expect_peek(&Token::RBracket)?;
+
+        Ok(Box::new(Expr::Index { left, index }))
+    }
+
+    /// フィールドアクセス式を解析する
+    fn parse_field_expr(&mut self, left: Box<Expr>) -> Result<Box<Expr>, ParseError> {
+        self.next();
+        let Some(Token::Identifier(name)) = self.current.clone() else {
+            return Err(self.unexpected_current());
+        };
+
+        Ok(Box::new(Expr::Field { left, name }))
+    }
+
+    /// 閉じ括弧endまでのカンマ区切りの式を解析する
+    fn parse_expr_list(&mut self, end: &Token) -> Result<Vec<Expr>, ParseError> {
+        let open = self.lexer.location(self.current_start);
+        let mut list = Vec::new();
+
+        if self.is_peek(end) {
+            self.next();
+            return Ok(list);
+        }
+
+        self.next();
+        list.push(*self.parse_list_element()?);
 
-        if token.is_none() {
-            Precedence::Lowest
+        while self.is_peek(&Token::Comma) {
+            self.next();
+            self.next();
+            list.push(*self.parse_list_element()?);
+        }
+
+        if end == &Token::RParen {
+            self.expect_close_paren(open)?;
         } else {
-            Precedence::from(token.as_ref().unwrap())
+            self.expect_peek(end)?;
+        }
+
+        Ok(list)
+    }
+
+    /// 配列リテラルや引数の要素を解析する
+    /// `...expr` はexprの配列の要素を展開する
+    fn parse_list_element(&mut self) -> Result<Box<Expr>, ParseError> {
+        if self.current()? != &Token::Spread {
+            return self.parse_expr(Precedence::Lowest);
         }
+
+        self.next();
+        let array = self.parse_expr(Precedence::Lowest)?;
+
+        Ok(Box::new(Expr::Spread(array)))
+    }
+
+    /// 次のトークンの優先度を返す
+    pub fn peeking_precedence(&self) -> Precedence {
+        self.peek().map(Precedence::from).unwrap_or(Precedence::Lowest)
     }
 
     /// 次のトークンが引数のトークンと同じかどうかを返す
     pub fn is_peek(&self, token: &Token) -> bool {
-        if self.peek.is_none() {
-            false
+        self.peek() == Some(token)
+    }
+
+    pub fn peeking_eof(&self) -> bool {
+        self.peek().is_none()
+    }
+
+    /// 次のトークンで文が終わるかどうかを返す
+    fn peeking_statement_end(&self) -> bool {
+        self.is_peek(&Token::NewLine)
+            || self.is_peek(&Token::RBrace)
+            || self.is_peek(&Token::Reserved(Reserved::Else))
+            || self.is_peek(&Token::Reserved(Reserved::Elif))
+            || self.peeking_eof()
+    }
+
+    /// 次のトークンが期待しているトークンであれば進める
+    fn expect_peek(&mut self, token: &Token) -> Result<(), ParseError> {
+        if self.is_peek(token) {
+            self.next();
+            Ok(())
         } else {
-            self.peek.as_ref().unwrap() == token
+            Err(self.unexpected_peek())
         }
     }
 
-    pub fn peeking_eof(&self) -> bool {
-        self.peek.is_none()
+    /// 次のトークンが)であれば進める
+    /// 行や入力が先に終わったときは，openの(が閉じられていないことを示す
+    fn expect_close_paren(&mut self, open: Position) -> Result<(), ParseError> {
+        if self.is_peek(&Token::NewLine) || self.peeking_eof() {
+            return Err(ParseError::UnclosedParen { position: open });
+        }
+
+        self.expect_peek(&Token::RParen)
+    }
+
+    /// 文が終わるべきところに次のトークンが続いていることを示すエラー
+    fn unexpected_statement_end(&self) -> ParseError {
+        match &self.lookahead[0] {
+            (Some(Token::RParen), start) => ParseError::UnmatchedParen {
+                position: self.lexer.location(*start),
+            },
+            _ => self.unexpected_peek(),
+        }
+    }
+
+    /// 現在のトークンを返す
+    fn current(&self) -> Result<&Token, ParseError> {
+        self.current.as_ref().ok_or_else(|| self.unexpected_current())
+    }
+
+    /// 現在のトークンが予期しないものであることを示すエラー
+    fn unexpected_current(&self) -> ParseError {
+        self.unexpected(self.current.clone(), self.current_start)
+    }
+
+    /// 次のトークンが予期しないものであることを示すエラー
+    fn unexpected_peek(&self) -> ParseError {
+        let (token, start) = self.lookahead[0].clone();
+        self.unexpected(token, start)
+    }
+
+    fn unexpected(&self, token: Option<Token>, start: usize) -> ParseError {
+        let position = self.lexer.location(start);
+        match token {
+            Some(Token::Error(error)) => ParseError::Lex { error, position },
+            Some(token) => ParseError::UnexpectedToken { token, position },
+            None => ParseError::UnexpectedEof { position },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(code: &str) -> ParseError {
+        Parser::new(Lexer::new(code)).parse().expect_err("expected parse error")
+    }
+
+    #[test]
+    fn unclosed_block_reports_opening_line() {
+        let error = parse_error("x = 1\nif x > 0 {\n  print x\n");
+        assert_eq!(error, ParseError::UnclosedBrace { position: Position { line: 2, column: 10 } });
+        assert_eq!(error.to_string(), "2:10: unclosed '{'");
+    }
+
+    /// 構文解析中に見つかった警告
+    fn warnings(code: &str) -> Vec<Warning> {
+        let mut parser = Parser::new(Lexer::new(code));
+        parser.parse().expect("failed to parse");
+        parser.warnings()
+    }
+
+    #[test]
+    fn assignment_in_condition_warns() {
+        assert_eq!(
+            warnings("if x = 5 { }"),
+            vec![Warning::AssignmentInCondition { position: Position { line: 1, column: 4 } }]
+        );
+        assert_eq!(warnings("if (x = 5) { }"), vec![]);
+        assert_eq!(warnings("if x == 5 { }"), vec![]);
+    }
+
+    /// 中置演算子の記号 (isは右辺に型名をとるため除く)
+    const SYMBOLS: &[&str] = &[
+        "=", "+=", "-=", "*=", "/=", "%=", "&&=", "||=", "<<=", ">>=", "??", "||", "&&", "|", "&", "==", "!=",
+        "===", ">", ">=", "<", "<=", "in", "<<", ">>", "+", "-", "*", "/", "%",
+    ];
+
+    fn parse_expr(code: &str) -> Expr {
+        *Parser::new(Lexer::new(code)).parse_single_expr().expect("failed to parse")
+    }
+
+    /// 式の木の形を，中置演算子式だけ括弧で囲んで表す
+    fn shape(expr: &Expr) -> String {
+        match expr {
+            Expr::InfixExpr { left, right, .. } => format!("({} {})", shape(left), shape(right)),
+            Expr::Conditional { condition, then_value, else_value } => {
+                format!("({} ? {} : {})", shape(condition), shape(then_value), shape(else_value))
+            }
+            Expr::Identifier(name) => name.clone(),
+            expr => panic!("unexpected expression {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn precedence_table_entries() {
+        assert_eq!(infix_binding(&Operator::Assign), Some((Precedence::Assign, Associativity::Right)));
+        assert_eq!(infix_binding(&Operator::Coalesce), Some((Precedence::Coalesce, Associativity::Right)));
+        assert_eq!(infix_binding(&Operator::Minus), Some((Precedence::Sum, Associativity::Left)));
+        assert_eq!(infix_binding(&Operator::Mul), Some((Precedence::Product, Associativity::Left)));
+        assert_eq!(infix_binding(&Operator::In), Some((Precedence::Compare, Associativity::Left)));
+        assert_eq!(infix_binding(&Operator::Not), None);
+
+        let covered = SYMBOLS.iter().map(|symbol| Operator::from(*symbol)).collect::<Vec<_>>();
+        for (operator, _, _) in INFIX_OPERATORS {
+            assert!(covered.contains(operator) || *operator == Operator::Is, "{:?}", operator);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn every_operator_respects_associativity() {
+        for symbol in SYMBOLS {
+            let (_, associativity) = infix_binding(&Operator::from(*symbol)).unwrap();
+            let expected = match associativity {
+                Associativity::Left => "((a b) c)",
+                Associativity::Right => "(a (b c))",
+            };
+            assert_eq!(shape(&parse_expr(&format!("a {} b {} c", symbol, symbol))), expected, "{}", symbol);
+        }
+    }
+
+    #[test]
+    fn higher_precedence_binds_tighter() {
+        for low in SYMBOLS {
+            for high in SYMBOLS {
+                let (low_precedence, _) = infix_binding(&Operator::from(*low)).unwrap();
+                let (high_precedence, _) = infix_binding(&Operator::from(*high)).unwrap();
+                if low_precedence >= high_precedence {
+                    continue;
+                }
+
+                let code = format!("a {} b {} c", low, high);
+                assert_eq!(shape(&parse_expr(&code)), "(a (b c))", "{}", code);
+                // 代入の左辺には演算子式を置けない
+                if low_precedence == Precedence::Assign {
+                    let code = format!("a {} b {} c", high, low);
+                    let error = Parser::new(Lexer::new(&code)).parse_single_expr();
+                    assert!(matches!(error, Err(ParseError::InvalidAssignmentTarget { .. })), "{}", code);
+                    continue;
+                }
+                let code = format!("a {} b {} c", high, low);
+                assert_eq!(shape(&parse_expr(&code)), "((a b) c)", "{}", code);
+            }
+        }
+    }
+
+    #[test]
+    fn peek_n_looks_ahead() {
+        let mut parser = Parser::new(Lexer::new("a + b"));
+        assert_eq!(parser.peek_n(3), None);
+        assert_eq!(parser.peek_n(2), Some(&Token::Identifier("b".to_string())));
+        assert_eq!(parser.peek_n(1), Some(&Token::Operator(Operator::Plus)));
+        assert_eq!(parser.peek_n(0), Some(&Token::Identifier("a".to_string())));
+
+        parser.next();
+        assert_eq!(parser.peek_n(0), Some(&Token::Operator(Operator::Plus)));
+        assert_eq!(parser.peek_n(1), Some(&Token::Identifier("b".to_string())));
+        assert_eq!(parser.peek_n(2), None);
+    }
+
+    #[test]
+    fn mismatched_parentheses_report_position() {
+        let error = parse_error("x = (1 + 2");
+        assert_eq!(error, ParseError::UnclosedParen { position: Position { line: 1, column: 5 } });
+        assert_eq!(error.to_string(), "1:5: unclosed '('");
+
+        let error = parse_error("1 + 2)");
+        assert_eq!(error, ParseError::UnmatchedParen { position: Position { line: 1, column: 6 } });
+        assert_eq!(error.to_string(), "1:6: unmatched ')'");
+
+        assert_eq!(parse_error("((1)"), ParseError::UnclosedParen { position: Position { line: 1, column: 1 } });
+    }
+
+    #[test]
+    fn top_level_statements_must_end() {
+        let error = parse_error("1 + 2 3");
+        assert_eq!(error, ParseError::UnexpectedToken { token: Token::Number(3.0), position: Position { line: 1, column: 7 } });
+        assert_eq!(parse_error("x = 1 y = 2"), ParseError::UnexpectedToken { token: Token::Identifier("y".to_string()), position: Position { line: 1, column: 7 } });
+
+        let program = Parser::new(Lexer::new("x = 5-1\nif x > 0 { x = 1 } y = 2")).parse().unwrap();
+        assert_eq!(program.len(), 3);
+        let Statement::Expr(expr) = &program[0] else { panic!("expected expression") };
+        let Expr::InfixExpr { right, .. } = expr.as_ref() else { panic!("expected assignment") };
+        assert!(matches!(right.as_ref(), Expr::InfixExpr { operator: Operator::Minus, .. }));
+    }
+
+    #[test]
+    fn conditional_nesting() {
+        assert_eq!(shape(&parse_expr("x = a ? b : c")), "(x (a ? b : c))");
+        assert_eq!(shape(&parse_expr("a ? b : c ? d : e")), "(a ? b : (c ? d : e))");
+        assert_eq!(shape(&parse_expr("a ? b ? c : d : e")), "(a ? (b ? c : d) : e)");
+        assert_eq!(shape(&parse_expr("a ? b : c = d")), "(a ? b : (c d))");
+        assert_eq!(shape(&parse_expr("a || b ? c : d")), "((a b) ? c : d)");
+    }
+
+    #[test]
+    fn assignment_rejects_invalid_targets() {
+        let error = parse_error("a[0] = 5");
+        assert_eq!(error, ParseError::InvalidAssignmentTarget { position: Position { line: 1, column: 6 } });
+        assert_eq!(error.to_string(), "1:6: invalid assignment target");
+
+        assert_eq!(parse_error("x, 1 = 1, 2"), ParseError::InvalidAssignmentTarget { position: Position { line: 1, column: 6 } });
+        assert_eq!(parse_error("f() += 1"), ParseError::InvalidAssignmentTarget { position: Position { line: 1, column: 5 } });
+        assert!(Parser::new(Lexer::new("a.b = 1\nx, a.c = 1, 2\na.b += 1")).parse().is_ok());
+    }
+
+    #[test]
+    fn is_rejects_unknown_type_name() {
+        let error = parse_error("x = 5 is banana");
+        assert_eq!(error, ParseError::UnknownType { name: "banana".to_string(), position: Position { line: 1, column: 10 } });
+        assert_eq!(error.to_string(), "1:10: unknown type name banana");
+    }
+}
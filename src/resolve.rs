@@ -0,0 +1,174 @@
+use std::rc::Rc;
+
+use crate::parse::{Expr, Function, Statement};
+
+/// 関数の本体の中で仮引数を参照する識別子を，仮引数の添字に置き換える
+/// 仮引数以外の変数は呼び出し元やグローバル変数を探す必要があるため，名前のまま残す
+pub fn resolve(statements: Vec<Statement>) -> Vec<Statement> {
+    resolve_statements(statements, &[])
+}
+
+/// parametersは実行中の関数の仮引数 (関数の外では空)
+fn resolve_statements(statements: Vec<Statement>, parameters: &[String]) -> Vec<Statement> {
+    statements
+        .into_iter()
+        .map(|statement| resolve_statement(statement, parameters))
+        .collect()
+}
+
+fn resolve_statement(statement: Statement, parameters: &[String]) -> Statement {
+    let resolve_expr = |expr: Box<Expr>| Box::new(resolve_expr(*expr, parameters));
+    let resolve_block = |block: Box<Statement>| Box::new(resolve_statement(*block, parameters));
+
+    match statement {
+        Statement::Return(expr) => Statement::Return(resolve_expr(expr)),
+        Statement::Print(arguments) => Statement::Print(resolve_exprs(arguments, parameters)),
+        Statement::Eprint(arguments) => Statement::Eprint(resolve_exprs(arguments, parameters)),
+        Statement::Expr(expr) => Statement::Expr(resolve_expr(expr)),
+        Statement::Block(statements) => Statement::Block(resolve_statements(statements, parameters)),
+        Statement::If { condition, block, else_block } => Statement::If {
+            condition: resolve_expr(condition),
+            block: resolve_block(block),
+            else_block: else_block.map(resolve_block),
+        },
+        Statement::While { condition, block, else_block } => Statement::While {
+            condition: resolve_expr(condition),
+            block: resolve_block(block),
+            else_block: else_block.map(resolve_block),
+        },
+        Statement::WhileLet { name, value, block } => Statement::WhileLet {
+            name,
+            value: resolve_expr(value),
+            block: resolve_block(block),
+        },
+        Statement::Break(value) => Statement::Break(value.map(resolve_expr)),
+        Statement::Continue | Statement::Include(_) => statement,
+        Statement::Loop(block) => Statement::Loop(resolve_block(block)),
+        Statement::Function { name, function } => Statement::Function {
+            name,
+            function: resolve_function(&function),
+        },
+        Statement::TryCatch { try_block, name, catch_block } => Statement::TryCatch {
+            try_block: resolve_block(try_block),
+            name,
+            catch_block: resolve_block(catch_block),
+        },
+    }
+}
+
+/// 関数の本体をその関数の仮引数で解決する
+/// 外側の関数の仮引数は，内側の関数からは名前で参照する
+fn resolve_function(function: &Function) -> Rc<Function> {
+    Rc::new(Function {
+        name: function.name.clone(),
+        parameters: function.parameters.clone(),
+        body: resolve_statements(function.body.clone(), &function.parameters),
+        pure: function.pure,
+    })
+}
+
+fn resolve_exprs(exprs: Vec<Expr>, parameters: &[String]) -> Vec<Expr> {
+    exprs.into_iter().map(|expr| resolve_expr(expr, parameters)).collect()
+}
+
+fn resolve_expr(expr: Expr, parameters: &[String]) -> Expr {
+    let resolve = |expr: Box<Expr>| Box::new(resolve_expr(*expr, parameters));
+
+    match expr {
+        Expr::Identifier(name) => match parameters.iter().position(|parameter| *parameter == name) {
+            Some(index) => Expr::Local(index),
+            None => Expr::Identifier(name),
+        },
+        Expr::PrefixExpr { operator, right } => Expr::PrefixExpr {
+            operator,
+            right: resolve(right),
+        },
+        Expr::InfixExpr { left, operator, right } => Expr::InfixExpr {
+            left: resolve(left),
+            operator,
+            right: resolve(right),
+        },
+        Expr::PostfixExpr { left, operator } => Expr::PostfixExpr {
+            left: resolve(left),
+            operator,
+        },
+        Expr::Typeof(operand) => Expr::Typeof(resolve(operand)),
+        Expr::Array(elements) => Expr::Array(resolve_exprs(elements, parameters)),
+        Expr::Tuple(elements) => Expr::Tuple(resolve_exprs(elements, parameters)),
+        Expr::Loop(block) => Expr::Loop(Box::new(resolve_statement(*block, parameters))),
+        Expr::Function(function) => Expr::Function(resolve_function(&function)),
+        Expr::Call { function, arguments } => Expr::Call {
+            function: resolve(function),
+            arguments: resolve_exprs(arguments, parameters),
+        },
+        Expr::Index { left, index } => Expr::Index {
+            left: resolve(left),
+            index: resolve(index),
+        },
+        Expr::Object(fields) => Expr::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name, resolve_expr(value, parameters)))
+                .collect(),
+        ),
+        Expr::Field { left, name } => Expr::Field {
+            left: resolve(left),
+            name,
+        },
+        Expr::Spread(array) => Expr::Spread(resolve(array)),
+        Expr::Conditional { condition, then_value, else_value } => Expr::Conditional {
+            condition: resolve(condition),
+            then_value: resolve(then_value),
+            else_value: resolve(else_value),
+        },
+        Expr::Local(_) | Expr::Number(_) | Expr::String(_) | Expr::Char(_) | Expr::Null | Expr::Boolean(_) => expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::tests::eval_with;
+    use crate::interpreter::Interpreter;
+    use crate::parse::Parser;
+    use crate::token::Lexer;
+    use crate::types::Primitive;
+
+    /// 名前を解決してから実行し，最後の式文の値を返す
+    fn eval_resolved(code: &str) -> Primitive {
+        let program = Parser::new(Lexer::new(code)).parse().expect("failed to parse");
+        Interpreter::new().run(&resolve(program)).expect("failed to run")
+    }
+
+    #[test]
+    fn resolved_program_gives_same_result() {
+        let programs = [
+            "fn add(a, b) { a + b }\nadd(2, 3)",
+            "fn fact(n) { if n <= 1 { return 1 }\nn * fact(n - 1) }\nfact(10)",
+            "scale = 3\nfn f(x) { x * scale }\nf(4)",
+            "x = 1\nfn f(x) { x = x + 1\nx }\nf(5) + x",
+            "fn outer(a) { inner = fn(b) { a + b }\ninner(10) }\nouter(1)",
+        ];
+
+        for code in programs {
+            let expected = eval_with(&mut Interpreter::new(), code).expect("failed to run");
+            assert_eq!(eval_resolved(code), expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn parameters_become_locals_and_globals_stay_names() {
+        let program = Parser::new(Lexer::new("fn f(x) { x + y }")).parse().unwrap();
+        let Statement::Function { function, .. } = &resolve(program)[0] else {
+            panic!("expected function");
+        };
+        let Statement::Expr(expr) = &function.body[0] else {
+            panic!("expected expression");
+        };
+        let Expr::InfixExpr { left, right, .. } = expr.as_ref() else {
+            panic!("expected infix expression");
+        };
+        assert!(matches!(**left, Expr::Local(0)));
+        assert!(matches!(**right, Expr::Identifier(ref name) if name == "y"));
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 /// 字句
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
@@ -7,6 +9,8 @@ pub enum Token {
     Number(f64),
     /// 文字列リテラル
     String(String),
+    /// 文字リテラル
+    Char(char),
 
     /// (
     LParen,
@@ -16,6 +20,20 @@ pub enum Token {
     LBrace,
     /// }
     RBrace,
+    /// [
+    LBracket,
+    /// ]
+    RBracket,
+    /// ,
+    Comma,
+    /// :
+    Colon,
+    /// ? (条件演算子)
+    Question,
+    /// .
+    Dot,
+    /// ... (配列の展開)
+    Spread,
 
     /// 演算子
     Operator(Operator),
@@ -25,6 +43,39 @@ pub enum Token {
 
     /// 改行
     NewLine,
+
+    /// コメント (`//` より後ろの行末までの文字列，keep_commentsのときだけ返す)
+    Comment(String),
+
+    /// 字句解析のエラー
+    Error(LexError),
+}
+
+/// 字句解析のエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    /// 解釈できない文字
+    UnexpectedCharacter(char),
+    /// 不正な数値リテラル
+    BadNumber(String),
+    /// 不正なエスケープシーケンス
+    BadEscape(String),
+    /// 1文字でない文字リテラル
+    BadChar(String),
+    /// 閉じられていない三重引用符の文字列リテラル
+    UnterminatedString,
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexError::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            LexError::BadNumber(literal) => write!(f, "invalid number literal '{}'", literal),
+            LexError::BadEscape(sequence) => write!(f, "invalid escape sequence '{}'", sequence),
+            LexError::BadChar(literal) => write!(f, "invalid character literal {}", literal),
+            LexError::UnterminatedString => write!(f, "unterminated string literal"),
+        }
+    }
 }
 
 /// 演算子
@@ -64,6 +115,10 @@ pub enum Operator {
     BitAnd,
     /// |
     BitOr,
+    /// <<
+    ShiftLeft,
+    /// >>
+    ShiftRight,
     /// =
     Assign,
     /// +=
@@ -76,6 +131,20 @@ pub enum Operator {
     DivAssign,
     /// %=
     ModAssign,
+    /// &&=
+    AndAssign,
+    /// ||=
+    OrAssign,
+    /// <<=
+    ShiftLeftAssign,
+    /// >>=
+    ShiftRightAssign,
+    /// ??
+    Coalesce,
+    /// in
+    In,
+    /// is (型の判定)
+    Is,
 }
 
 impl From<&str> for Operator {
@@ -98,12 +167,21 @@ impl From<&str> for Operator {
             "!" => Operator::Not,
             "&" => Operator::BitAnd,
             "|" => Operator::BitOr,
+            "<<" => Operator::ShiftLeft,
+            ">>" => Operator::ShiftRight,
             "=" => Operator::Assign,
             "+=" => Operator::AddAssign,
             "-=" => Operator::SubAssign,
             "*=" => Operator::MulAssign,
             "/=" => Operator::DivAssign,
             "%=" => Operator::ModAssign,
+            "&&=" => Operator::AndAssign,
+            "||=" => Operator::OrAssign,
+            "<<=" => Operator::ShiftLeftAssign,
+            ">>=" => Operator::ShiftRightAssign,
+            "??" => Operator::Coalesce,
+            "in" => Operator::In,
+            "is" => Operator::Is,
             _ => panic!("{} is not operator", s),
         }
     }
@@ -111,10 +189,14 @@ impl From<&str> for Operator {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[allow(dead_code)]
 pub enum Reserved {
     /// print文
     Print,
 
+    /// eprint文 (標準エラー出力に表示する)
+    Eprint,
+
     // return
     Return,
 
@@ -127,6 +209,9 @@ pub enum Reserved {
     // else
     Else,
 
+    // elif (else ifと同じ)
+    Elif,
+
     // for
     For,
 
@@ -139,56 +224,250 @@ pub enum Reserved {
     // continue
     Continue,
 
+    // loop
+    Loop,
+
+    // include
+    Include,
+
+    // try
+    Try,
+
+    // catch
+    Catch,
+
+    // let
+    Let,
+
     // function
     Fn,
+
+    // pure (副作用のない関数の修飾子)
+    Pure,
+
+    // null
+    Null,
+
+    // true
+    True,
+
+    // false
+    False,
+}
+
+/// ソースコード上の位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// 行 (1始まり)
+    pub line: usize,
+    /// 列 (1始まり)
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// 警告
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// f64で正確に表せない整数リテラル
+    ImpreciseNumber {
+        literal: String,
+        position: Position,
+    },
+    /// if文，while文の条件式での代入
+    AssignmentInCondition {
+        position: Position,
+    },
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::ImpreciseNumber { literal, position } => write!(
+                f,
+                "{}: integer literal {} cannot be represented exactly",
+                position, literal
+            ),
+            Warning::AssignmentInCondition { position } => write!(
+                f,
+                "{}: assignment used as condition (use == to compare, or parenthesize to silence)",
+                position
+            ),
+        }
+    }
 }
 
 /// 字句解析器
 #[derive(Debug)]
 pub struct Lexer {
-    /// 文字の配列
-    pub tokens: Vec<char>,
+    /// ソースコード
+    source: String,
 
-    /// 現在解析中の文字の位置
+    /// 現在解析中の文字の位置 (バイト単位)
     position: usize,
 
     /// 現在解析中の文字
     current: Option<char>,
+
+    /// 現在解析中の文字の次の文字
+    peeked: Option<char>,
+
+    /// 直前に読み込んだトークンの開始位置
+    token_start: usize,
+
+    /// 字句解析中に見つかった警告
+    warnings: Vec<Warning>,
+
+    /// コメントを読み飛ばさずにトークンとして返す
+    keep_comments: bool,
+
+    /// 直前のトークンが値の終わり (数値，識別子，閉じ括弧など) かどうか
+    /// そのときは `5-1` の `-1` を負の数ではなく減算として読み込む
+    after_operand: bool,
 }
 
 impl Lexer {
-    pub fn new(input: Vec<char>) -> Self {
-        let first = input.first().cloned();
-        Lexer {
-            tokens: input,
+    pub fn new(source: &str) -> Self {
+        let mut lexer = Lexer {
+            source: source.to_string(),
             position: 0,
-            current: first,
-        }
+            current: None,
+            peeked: None,
+            token_start: 0,
+            warnings: Vec::new(),
+            keep_comments: false,
+            after_operand: false,
+        };
+        lexer.seek(0);
+
+        lexer
+    }
+
+    /// コメントをトークンとして返すかどうかを設定する
+    /// 整形ツールなどがコメントを保持するために使う
+    pub fn set_keep_comments(&mut self, keep_comments: bool) {
+        self.keep_comments = keep_comments;
     }
 
     /// トークンを1つ返す
     pub fn token(&mut self) -> Option<Token> {
         self.skip_whitespace();
+        self.token_start = self.position;
 
         let token = self.number()
+                .or_else(|| self.color())
                 .or_else(|| self.new_line())
                 .or_else(|| self.paren())
+                .or_else(|| self.comma())
                 .or_else(|| self.reserved()) 
+                .or_else(|| self.comment())
                 .or_else(|| self.operator())
                 .or_else(|| self.string_literal())
-                .or_else(|| self.identifier());
+                .or_else(|| self.char_literal())
+                .or_else(|| self.identifier())
+                .or_else(|| self.illegal());
         self.next();
 
         // dbg!(token.clone());
 
+        if !matches!(token, Some(Token::Comment(_))) {
+            self.after_operand = matches!(
+                token,
+                Some(
+                    Token::Identifier(_)
+                        | Token::Number(_)
+                        | Token::String(_)
+                        | Token::Char(_)
+                        | Token::RParen
+                        | Token::RBracket
+                        | Token::RBrace
+                        | Token::Reserved(Reserved::True | Reserved::False | Reserved::Null)
+                )
+            );
+        }
+
         token
     }
 
-    /// 空白をスキップする
+    /// 先頭に戻り，同じ入力をはじめから読み直せるようにする
+    /// 見つかった警告も破棄する
+    #[allow(dead_code)]
+    pub fn reset(&mut self) {
+        self.seek(0);
+        self.token_start = 0;
+        self.after_operand = false;
+        self.warnings.clear();
+    }
+
+    /// 現在解析中の文字の位置 (バイト単位)
+    #[allow(dead_code)]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// 直前に読み込んだトークンの開始位置 (バイト単位)
+    pub fn token_start(&self) -> usize {
+        self.token_start
+    }
+
+    /// 字句解析中に見つかった警告
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// 文字の位置を行と列に変換する
+    pub fn location(&self, index: usize) -> Position {
+        let mut position = Position { line: 1, column: 1 };
+        let before = self.source.get(..index).unwrap_or(&self.source);
+        for c in before.chars() {
+            if c == '\n' {
+                position.line += 1;
+                position.column = 1;
+            } else {
+                position.column += 1;
+            }
+        }
+
+        position
+    }
+
+    /// 空白とコメントをスキップする
     fn skip_whitespace(&mut self) {
-        while self.current.is_some() && is_space(self.current.unwrap()) {
+        loop {
+            while self.current.is_some() && is_space(self.current.unwrap()) {
+                self.next();
+            }
+
+            if self.keep_comments || self.current != Some('/') || !self.is_peeking(&'/') {
+                break;
+            }
+
+            // `//` から行末まではコメント
+            while self.current.is_some() && self.current != Some('\n') {
+                self.next();
+            }
+        }
+    }
+
+    /// コメントを読み込む
+    /// keep_commentsでなければskip_whitespaceで読み飛ばされるため，ここには来ない
+    fn comment(&mut self) -> Option<Token> {
+        if self.current? != '/' || !self.is_peeking(&'/') {
+            return None;
+        }
+
+        self.next();
+        let mut text = String::new();
+        while !matches!(self.peek(), None | Some('\n')) {
             self.next();
+            text.push(self.current?);
         }
+
+        Some(Token::Comment(text))
     }
 
     fn new_line(&mut self) -> Option<Token> {
@@ -202,47 +481,155 @@ impl Lexer {
     /// 予約語を読み込む
     fn reserved(&mut self) -> Option<Token> {
         match self.current? {
-            'p' => self.check_string_with_space("print").then_some(Token::Reserved(Reserved::Print)),
+            'p' => self.check_keyword("print").then_some(Token::Reserved(Reserved::Print))
+                .or_else(|| self.check_keyword("pure").then_some(Token::Reserved(Reserved::Pure))),
             'r' => self.check_string_with_space("return").then_some(Token::Reserved(Reserved::Return)),
-            'i' => self.check_string_with_space("if").then_some(Token::Reserved(Reserved::If)),
-            'e' => self.check_string("else").then_some(Token::Reserved(Reserved::Else)),
+            'i' => self.check_string_with_space("if").then_some(Token::Reserved(Reserved::If))
+                .or_else(|| self.check_keyword("include").then_some(Token::Reserved(Reserved::Include))),
+            'e' => self.check_keyword("else").then_some(Token::Reserved(Reserved::Else))
+                .or_else(|| self.check_keyword("elif").then_some(Token::Reserved(Reserved::Elif)))
+                .or_else(|| self.check_keyword("eprint").then_some(Token::Reserved(Reserved::Eprint))),
             'f' => self.check_string_with_space("for").then_some(Token::Reserved(Reserved::For))
-                .or_else(|| self.check_string_with_space("fn").then_some(Token::Reserved(Reserved::Fn))),
-            't' => self.check_string_with_space("typeof").then_some(Token::Reserved(Reserved::Typeof)),
+                .or_else(|| self.check_keyword("fn").then_some(Token::Reserved(Reserved::Fn)))
+                .or_else(|| self.check_keyword("false").then_some(Token::Reserved(Reserved::False))),
+            't' => self.check_keyword("typeof").then_some(Token::Reserved(Reserved::Typeof))
+                .or_else(|| self.check_keyword("try").then_some(Token::Reserved(Reserved::Try)))
+                .or_else(|| self.check_keyword("true").then_some(Token::Reserved(Reserved::True))),
+            'w' => self.check_keyword("while").then_some(Token::Reserved(Reserved::While)),
+            'b' => self.check_keyword("break").then_some(Token::Reserved(Reserved::Break)),
+            'c' => self.check_keyword("continue").then_some(Token::Reserved(Reserved::Continue))
+                .or_else(|| self.check_keyword("catch").then_some(Token::Reserved(Reserved::Catch))),
+            'n' => self.check_keyword("null").then_some(Token::Reserved(Reserved::Null)),
+            'l' => self.check_keyword("loop").then_some(Token::Reserved(Reserved::Loop))
+                .or_else(|| self.check_keyword("let").then_some(Token::Reserved(Reserved::Let))),
             _ => None,
         }
     }
 
+    /// 空白が続く予約語かどうかを確認し，空白まで進める
     fn check_string_with_space(&mut self, s: &str) -> bool {
-        let s_with_space = s.to_owned() + " ";
-        self.check_string(&s_with_space)
+        if self.source.as_bytes().get(self.position + s.len()) != Some(&b' ') || !self.check_string(s) {
+            return false;
+        }
+        self.next();
+
+        true
+    }
+
+    /// 識別子の一部でない予約語かどうかを確認する
+    fn check_keyword(&mut self, s: &str) -> bool {
+        let following = self.char_at(self.position + s.len());
+        if following.is_some_and(|c| is_identifier_continue(&c)) {
+            return false;
+        }
+
+        self.check_string(s)
     }
 
+    /// 現在の位置からsが続いていれば，sの最後の文字まで進める
     fn check_string(&mut self, s: &str) -> bool {
-        for (i, char) in s.chars().enumerate() {
-            if self.tokens.get(self.position + i) != Some(&char) {
-                return false;
-            }
+        let matches = self.source.get(self.position..).is_some_and(|rest| rest.starts_with(s));
+        if !matches {
+            return false;
         }
 
-        self.position += s.len() - 1;
+        let last = s.chars().last().map_or(0, char::len_utf8);
+        self.seek(self.position + s.len() - last);
 
         true
     }
 
     /// 数字を読み込む
     fn number(&mut self) -> Option<Token> {
-        let mut number_chars = vec![self.current?];
+        let first = self.current?;
+        let starts_number = first.is_ascii_digit()
+            || (first == '-' && !self.after_operand && self.peek().is_some_and(char::is_ascii_digit))
+            || (first == '.' && self.peek().is_some_and(char::is_ascii_digit));
+        if !starts_number {
+            return None;
+        }
+
+        if first == '0' {
+            if let Some(radix) = self.peek().and_then(radix_of) {
+                self.next();
+                return Some(self.radix_number(radix));
+            }
+        }
 
-        while self.peek().is_some() && is_part_of_number(self.peek()?) {
+        while self.peek().is_some() && (is_part_of_number(self.peek()?) || self.is_peeking(&'_')) {
             self.next();
-            number_chars.push(self.current?);
         }
 
-        String::from_iter(number_chars)
-            .parse::<f64>()
-            .ok()
-            .map(Token::Number)
+        let literal = self.current_slice().to_string();
+        if !has_valid_separators(&literal) {
+            return Some(Token::Error(LexError::BadNumber(literal)));
+        }
+
+        let digits = literal.replace('_', "");
+        let Ok(number) = digits.parse::<f64>() else {
+            return Some(Token::Error(LexError::BadNumber(literal)));
+        };
+
+        if !digits.contains('.') && !is_exact_integer(&digits, number) {
+            self.warnings.push(Warning::ImpreciseNumber {
+                literal,
+                position: self.location(self.token_start),
+            });
+        }
+
+        Some(Token::Number(number))
+    }
+
+    /// 0x, 0b, 0o, 0dの接頭辞がついた整数を読み込む
+    /// currentは接頭辞の2文字目を指している
+    fn radix_number(&mut self, radix: u32) -> Token {
+        let mut literal = String::from_iter(['0', self.current.unwrap()]);
+        let mut digits = String::new();
+
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+            self.next();
+            digits.push(self.current.unwrap());
+        }
+        literal.push_str(&digits);
+
+        if digits.is_empty() || !has_valid_separators(&digits) {
+            return Token::Error(LexError::BadNumber(literal));
+        }
+
+        let Ok(number) = u64::from_str_radix(&digits.replace('_', ""), radix) else {
+            return Token::Error(LexError::BadNumber(literal));
+        };
+
+        if !is_exact_integer(&number.to_string(), number as f64) {
+            self.warnings.push(Warning::ImpreciseNumber {
+                literal,
+                position: self.location(self.token_start),
+            });
+        }
+
+        Token::Number(number as f64)
+    }
+
+    /// #RRGGBB形式の色を24ビットの整数として読み込む
+    fn color(&mut self) -> Option<Token> {
+        if self.current? != '#' || !self.peek().is_some_and(char::is_ascii_alphanumeric) {
+            return None;
+        }
+
+        let mut digits = String::new();
+        while self.peek().is_some_and(char::is_ascii_alphanumeric) {
+            self.next();
+            digits.push(self.current?);
+        }
+
+        if digits.len() != 6 {
+            return Some(Token::Error(LexError::BadNumber(format!("#{}", digits))));
+        }
+
+        match u32::from_str_radix(&digits, 16) {
+            Ok(color) => Some(Token::Number(color as f64)),
+            Err(_) => Some(Token::Error(LexError::BadNumber(format!("#{}", digits)))),
+        }
     }
 
     /// 括弧を読み込む
@@ -252,6 +639,21 @@ impl Lexer {
             ')' => Some(Token::RParen),
             '{' => Some(Token::LBrace),
             '}' => Some(Token::RBrace),
+            '[' => Some(Token::LBracket),
+            ']' => Some(Token::RBracket),
+            _ => None,
+        }
+    }
+
+    /// カンマ，コロン，ドットを読み込む
+    fn comma(&mut self) -> Option<Token> {
+        match self.current? {
+            ',' => Some(Token::Comma),
+            ':' => Some(Token::Colon),
+            '.' if self.check_string("...") => Some(Token::Spread),
+            '.' => Some(Token::Dot),
+            // ??は演算子として読み込む
+            '?' if !self.is_peeking(&'?') => Some(Token::Question),
             _ => None,
         }
     }
@@ -265,11 +667,15 @@ impl Lexer {
             '/' => self.tokenize_operator(&["/=", "/"]),
             '%' => self.tokenize_operator(&["%=", "%"]),
             '=' => self.tokenize_operator(&["===", "==", "="]),
-            '>' => self.tokenize_operator(&[">=", ">"]),
-            '<' => self.tokenize_operator(&["<=", "<"]),
-            '&' => self.tokenize_operator(&["&&", "&"]),
-            '|' => self.tokenize_operator(&["||", "|"]),
+            // 長いものから順に試す
+            '>' => self.tokenize_operator(&[">>=", ">>", ">=", ">"]),
+            '<' => self.tokenize_operator(&["<<=", "<<", "<=", "<"]),
+            '&' => self.tokenize_operator(&["&&=", "&&", "&"]),
+            '|' => self.tokenize_operator(&["||=", "||", "|"]),
             '!' => self.tokenize_operator(&["!=", "!"]),
+            '?' => self.tokenize_operator(&["??"]),
+            'i' => self.check_keyword("in").then_some(Token::Operator(Operator::In))
+                .or_else(|| self.check_keyword("is").then_some(Token::Operator(Operator::Is))),
             _ => None,
         }
     }
@@ -287,14 +693,20 @@ impl Lexer {
 
     /// 識別子を読み込む
     fn identifier(&mut self) -> Option<Token> {
-        let mut identifier_chars = vec![self.current?];
+        if !is_identifier_start(&self.current?) {
+            return None;
+        }
 
-        while self.peek().is_some() && !self.peek().unwrap().is_whitespace() {
+        while self.peek().is_some() && is_identifier_continue(self.peek()?) {
             self.next();
-            identifier_chars.push(self.current?);
         }
 
-        Some(Token::Identifier(String::from_iter(identifier_chars)))
+        Some(Token::Identifier(self.current_slice().to_string()))
+    }
+
+    /// 解釈できない文字を読み込む
+    fn illegal(&mut self) -> Option<Token> {
+        self.current.map(|c| Token::Error(LexError::UnexpectedCharacter(c)))
     }
 
     /// 文字列リテラルを読み込む
@@ -303,28 +715,210 @@ impl Lexer {
             return None;
         }
 
-        let mut string_chars = vec![];
+        if self.is_triple_quote(self.position) {
+            return Some(self.triple_quoted_string());
+        }
+
+        let mut string = String::new();
 
         while self.peek().is_some() && self.peek() != Some(&'"') {
             self.next();
-            string_chars.push(self.current?);
+            if self.current? != '\\' {
+                string.push(self.current?);
+                continue;
+            }
+
+            match self.escape_sequence() {
+                Ok(c) => string.push(c),
+                Err(error) => {
+                    // 文字列の残りを読み飛ばす
+                    while self.peek().is_some() && self.peek() != Some(&'"') {
+                        self.next();
+                    }
+                    self.next();
+                    return Some(Token::Error(error));
+                }
+            }
+        }
+
+        self.next();
+
+        Some(Token::String(string))
+    }
+
+    /// """で囲まれた文字列リテラルを読み込む
+    /// 改行をそのまま含み，エスケープシーケンスは解釈しない
+    fn triple_quoted_string(&mut self) -> Token {
+        self.next();
+        self.next();
+
+        let mut string = String::new();
+        while !self.is_triple_quote(self.next_position()) {
+            match self.peek() {
+                Some(c) => string.push(*c),
+                None => return Token::Error(LexError::UnterminatedString),
+            }
+            self.next();
         }
 
+        self.next();
+        self.next();
         self.next();
 
-        Some(Token::String(String::from_iter(string_chars)))
+        Token::String(string)
+    }
+
+    /// positionから"""が続くかどうか
+    fn is_triple_quote(&self, position: usize) -> bool {
+        self.source.get(position..).is_some_and(|rest| rest.starts_with("\"\"\""))
+    }
+
+    /// 文字リテラルを読み込む
+    /// ''の間にはちょうど1文字(エスケープシーケンスでもよい)が必要
+    fn char_literal(&mut self) -> Option<Token> {
+        if self.current? != '\'' {
+            return None;
+        }
+        let start = self.position;
+
+        let value = match self.peek() {
+            None | Some('\n') | Some('\'') => None,
+            Some('\\') => {
+                self.next();
+                match self.escape_sequence() {
+                    Ok(c) => Some(c),
+                    Err(error) => {
+                        self.skip_char_literal();
+                        return Some(Token::Error(error));
+                    }
+                }
+            }
+            Some(_) => {
+                self.next();
+                self.current
+            }
+        };
+
+        if value.is_some() && self.is_peeking(&'\'') {
+            self.next();
+            return value.map(Token::Char);
+        }
+
+        self.skip_char_literal();
+        let literal = self.source[start..self.next_position().min(self.source.len())].to_string();
+        Some(Token::Error(LexError::BadChar(literal)))
+    }
+
+    /// 文字リテラルの閉じ引用符まで読み飛ばす
+    /// 同じ行に閉じ引用符がなければ行末の手前で止まる
+    fn skip_char_literal(&mut self) {
+        while !matches!(self.peek(), None | Some('\n')) {
+            self.next();
+            if self.current == Some('\'') {
+                return;
+            }
+        }
+    }
+
+    /// \から始まるエスケープシーケンスを解析する
+    /// \n, \t, \r, \0, \\, \", \', \xHH(0x7Fまで), \u{HHHHHH}に対応する
+    fn escape_sequence(&mut self) -> Result<char, LexError> {
+        let mut sequence = String::from("\\");
+        self.next();
+        let Some(c) = self.current else {
+            return Err(LexError::BadEscape(sequence));
+        };
+        sequence.push(c);
+
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            '\'' => Ok('\''),
+            'x' => {
+                let digits = self.hex_digits(2);
+                sequence.push_str(&digits);
+                match u32::from_str_radix(&digits, 16) {
+                    Ok(code) if digits.len() == 2 && code <= 0x7F => Ok(code as u8 as char),
+                    _ => Err(LexError::BadEscape(sequence)),
+                }
+            }
+            'u' => {
+                if !self.is_peeking(&'{') {
+                    return Err(LexError::BadEscape(sequence));
+                }
+                self.next();
+                sequence.push('{');
+
+                let digits = self.hex_digits(6);
+                sequence.push_str(&digits);
+                if !self.is_peeking(&'}') {
+                    return Err(LexError::BadEscape(sequence));
+                }
+                self.next();
+                sequence.push('}');
+
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or(LexError::BadEscape(sequence))
+            }
+            _ => Err(LexError::BadEscape(sequence)),
+        }
+    }
+
+    /// 最大max文字の16進数の数字を読み進める
+    fn hex_digits(&mut self, max: usize) -> String {
+        let mut digits = String::new();
+        while digits.len() < max {
+            match self.peek() {
+                Some(c) if c.is_ascii_hexdigit() => digits.push(*c),
+                _ => break,
+            }
+            self.next();
+        }
+        digits
     }
 
     /// positionを進め，
     /// currentを更新する
     pub fn next(&mut self) {
-        self.position += 1;
-        self.current = self.tokens.get(self.position).cloned();
+        self.seek(self.next_position());
     }
 
     /// 現在解析中の文字の次の文字
     pub fn peek(&self) -> Option<&char> {
-        self.tokens.get(self.position + 1)
+        self.peeked.as_ref()
+    }
+
+    /// positionに移動し，current，peekedを更新する
+    fn seek(&mut self, position: usize) {
+        self.position = position;
+        self.current = self.char_at(position);
+        self.peeked = self.char_at(self.next_position());
+    }
+
+    /// 現在解析中の文字の次の文字の位置
+    /// 入力の終わりを過ぎても1バイトずつ進む
+    fn next_position(&self) -> usize {
+        self.position + self.current.map_or(1, char::len_utf8)
+    }
+
+    /// position (バイト単位) にある文字
+    /// ASCII文字はUTF-8として復号せずにそのまま返す
+    fn char_at(&self, position: usize) -> Option<char> {
+        match self.source.as_bytes().get(position)? {
+            byte if byte.is_ascii() => Some(*byte as char),
+            _ => self.source.get(position..).and_then(|rest| rest.chars().next()),
+        }
+    }
+
+    /// 読み込み中のトークンの開始位置から現在の文字までの部分
+    fn current_slice(&self) -> &str {
+        &self.source[self.token_start..self.next_position()]
     }
 
     /// 次の文字が期待している文字かどうか
@@ -333,11 +927,295 @@ impl Lexer {
     }
 }
 
+/// 整数リテラルがf64で正確に表されているかどうか
+fn is_exact_integer(literal: &str, number: f64) -> bool {
+    let digits = literal.trim_start_matches('-').trim_start_matches('0');
+    let digits = if digits.is_empty() { "0" } else { digits };
+
+    format!("{:.0}", number.abs()) == digits
+}
+
+/// 数値リテラルの接頭辞に対応する基数
+fn radix_of(c: &char) -> Option<u32> {
+    match c {
+        'x' | 'X' => Some(16),
+        'b' | 'B' => Some(2),
+        'o' | 'O' => Some(8),
+        'd' | 'D' => Some(10),
+        _ => None,
+    }
+}
+
+/// 区切り文字_が数字の間にだけ使われているかどうか
+fn has_valid_separators(literal: &str) -> bool {
+    !literal.starts_with('_')
+        && !literal.ends_with('_')
+        && !literal.contains("__")
+        && !literal.contains("_.")
+        && !literal.contains("._")
+}
+
 /// 数字かどうか
 fn is_part_of_number(c: &char) -> bool {
     c.is_ascii_digit() || *c == '.'
 }
 
+/// 識別子の先頭になれる文字かどうか
+fn is_identifier_start(c: &char) -> bool {
+    c.is_alphabetic() || *c == '_'
+}
+
+/// 識別子の2文字目以降になれる文字かどうか
+fn is_identifier_continue(c: &char) -> bool {
+    c.is_alphanumeric() || *c == '_'
+}
+
 fn is_space(c: char) -> bool {
     c == ' ' || c == '\t'
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// コードのトークン列
+    fn tokens(code: &str) -> Vec<Token> {
+        let mut lexer = Lexer::new(code);
+        std::iter::from_fn(|| lexer.token()).collect()
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        assert_eq!(
+            tokens("数値 = π"),
+            vec![
+                Token::Identifier("数値".to_string()),
+                Token::Operator(Operator::Assign),
+                Token::Identifier("π".to_string()),
+            ]
+        );
+    }
+
+    /// コードを字句解析し，見つかった警告を返す
+    fn warnings(code: &str) -> Vec<Warning> {
+        let mut lexer = Lexer::new(code);
+        while lexer.token().is_some() {}
+        lexer.warnings().to_vec()
+    }
+
+    #[test]
+    fn imprecise_integer_literal_warns() {
+        assert_eq!(
+            warnings("x = 9007199254740993"),
+            vec![Warning::ImpreciseNumber {
+                literal: "9007199254740993".to_string(),
+                position: Position { line: 1, column: 5 },
+            }]
+        );
+        assert_eq!(warnings("x = 9007199254740992"), vec![]);
+    }
+
+    #[test]
+    fn decimal_prefix_and_separators() {
+        assert_eq!(tokens("0d100"), vec![Token::Number(100.0)]);
+        assert_eq!(tokens("0d1_000"), vec![Token::Number(1000.0)]);
+        assert_eq!(tokens("1_000_000"), vec![Token::Number(1000000.0)]);
+        assert_eq!(tokens("0d"), vec![Token::Error(LexError::BadNumber("0d".to_string()))]);
+        assert_eq!(tokens("0d_1"), vec![Token::Error(LexError::BadNumber("0d_1".to_string()))]);
+        assert_eq!(tokens("1__0"), vec![Token::Error(LexError::BadNumber("1__0".to_string()))]);
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(tokens(r#""\x41""#), vec![Token::String("A".to_string())]);
+        assert_eq!(tokens(r#""\u{1F600}""#), vec![Token::String("😀".to_string())]);
+        assert_eq!(tokens(r#""a\tb\n""#), vec![Token::String("a\tb\n".to_string())]);
+    }
+
+    #[test]
+    fn malformed_escape_sequences() {
+        let bad_escape = |sequence: &str| Token::Error(LexError::BadEscape(sequence.to_string()));
+        assert_eq!(tokens(r#""\x4""#)[0], bad_escape(r"\x4"));
+        assert_eq!(tokens(r#""\x80""#)[0], bad_escape(r"\x80"));
+        assert_eq!(tokens(r#""\u{D800}""#)[0], bad_escape(r"\u{D800}"));
+        assert_eq!(tokens(r#""\u41""#)[0], bad_escape(r"\u"));
+        assert_eq!(tokens(r#""\q""#)[0], bad_escape(r"\q"));
+    }
+
+    #[test]
+    fn char_literals() {
+        assert_eq!(tokens("'a'"), vec![Token::Char('a')]);
+        assert_eq!(tokens(r"'\n'"), vec![Token::Char('\n')]);
+        assert_eq!(tokens("'é'"), vec![Token::Char('é')]);
+    }
+
+    #[test]
+    fn malformed_char_literals() {
+        assert_eq!(tokens("''"), vec![Token::Error(LexError::BadChar("''".to_string()))]);
+        assert_eq!(tokens("'ab'"), vec![Token::Error(LexError::BadChar("'ab'".to_string()))]);
+        assert_eq!(tokens("'a"), vec![Token::Error(LexError::BadChar("'a".to_string()))]);
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        assert_eq!(tokens("1.2.3"), vec![Token::Error(LexError::BadNumber("1.2.3".to_string()))]);
+        assert_eq!(tokens("1..2"), vec![Token::Error(LexError::BadNumber("1..2".to_string()))]);
+        assert_eq!(tokens("1.5"), vec![Token::Number(1.5)]);
+        assert_eq!(tokens(".5"), vec![Token::Number(0.5)]);
+        assert_eq!(tokens("-2"), vec![Token::Number(-2.0)]);
+    }
+
+    #[test]
+    fn minus_after_operand_is_subtraction() {
+        let minus = Token::Operator(Operator::Minus);
+        assert_eq!(tokens("5-1"), vec![Token::Number(5.0), minus.clone(), Token::Number(1.0)]);
+        assert_eq!(tokens("x-1"), vec![Token::Identifier("x".to_string()), minus.clone(), Token::Number(1.0)]);
+        assert_eq!(tokens("(2)-1"), vec![Token::LParen, Token::Number(2.0), Token::RParen, minus, Token::Number(1.0)]);
+        assert_eq!(tokens("2 * -1"), vec![Token::Number(2.0), Token::Operator(Operator::Mul), Token::Number(-1.0)]);
+        assert_eq!(tokens("f(-1)")[2], Token::Number(-1.0));
+    }
+
+    #[test]
+    fn boolean_keywords() {
+        assert_eq!(tokens("true false truth"), vec![
+            Token::Reserved(Reserved::True),
+            Token::Reserved(Reserved::False),
+            Token::Identifier("truth".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn else_keyword_boundary() {
+        let expected = vec![Token::RBrace, Token::Reserved(Reserved::Else), Token::LBrace];
+        assert_eq!(tokens("} else {"), expected);
+        assert_eq!(tokens("}else{"), expected);
+        assert_eq!(tokens("elsewhere"), vec![Token::Identifier("elsewhere".to_string())]);
+    }
+
+    #[test]
+    fn reset_rereads_same_tokens() {
+        let mut lexer = Lexer::new("x = 9007199254740993\ny");
+        let first: Vec<_> = std::iter::from_fn(|| lexer.token()).collect();
+        assert_eq!(lexer.warnings().len(), 1);
+
+        lexer.reset();
+        assert_eq!(lexer.position(), 0);
+        assert!(lexer.warnings().is_empty());
+        assert_eq!(lexer.token(), Some(Token::Identifier("x".to_string())));
+        assert_eq!(lexer.position(), 1);
+
+        lexer.reset();
+        let second: Vec<_> = std::iter::from_fn(|| lexer.token()).collect();
+        assert_eq!(first, second);
+        assert_eq!(lexer.warnings().len(), 1);
+    }
+
+    #[test]
+    fn compound_shift_assignment_is_longest_match() {
+        assert_eq!(tokens("a>>=b"), vec![
+            Token::Identifier("a".to_string()),
+            Token::Operator(Operator::ShiftRightAssign),
+            Token::Identifier("b".to_string()),
+        ]);
+        assert_eq!(tokens("a<<=b")[1], Token::Operator(Operator::ShiftLeftAssign));
+        assert_eq!(tokens("a>>b")[1], Token::Operator(Operator::ShiftRight));
+        assert_eq!(tokens("a>=b")[1], Token::Operator(Operator::GreaterThanEqual));
+        assert_eq!(tokens("a >"), vec![Token::Identifier("a".to_string()), Token::Operator(Operator::GreaterThan)]);
+    }
+
+    #[test]
+    fn color_literals() {
+        assert_eq!(tokens("#FF0000"), vec![Token::Number(16711680.0)]);
+        assert_eq!(tokens("#ZZZ"), vec![Token::Error(LexError::BadNumber("#ZZZ".to_string()))]);
+        assert_eq!(tokens("#FFF"), vec![Token::Error(LexError::BadNumber("#FFF".to_string()))]);
+        assert_eq!(tokens("# 1")[0], Token::Error(LexError::UnexpectedCharacter('#')));
+    }
+
+    #[test]
+    fn comments_are_tokens_only_when_kept() {
+        let code = "x = 1 // one\n// alone\ny";
+        assert!(!tokens(code).iter().any(|token| matches!(token, Token::Comment(_))));
+
+        let mut lexer = Lexer::new(code);
+        lexer.set_keep_comments(true);
+        assert_eq!(
+            std::iter::from_fn(|| lexer.token()).collect::<Vec<_>>(),
+            vec![
+                Token::Identifier("x".to_string()),
+                Token::Operator(Operator::Assign),
+                Token::Number(1.0),
+                Token::Comment(" one".to_string()),
+                Token::NewLine,
+                Token::Comment(" alone".to_string()),
+                Token::NewLine,
+                Token::Identifier("y".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn triple_quoted_strings() {
+        assert_eq!(
+            tokens("\"\"\"line 1\n\"quoted\" 'single'\\n\"\"\""),
+            vec![Token::String("line 1\n\"quoted\" 'single'\\n".to_string())]
+        );
+        assert_eq!(tokens("\"\"\"abc\n\"\""), vec![Token::Error(LexError::UnterminatedString)]);
+    }
+
+    #[test]
+    fn token_starts_are_byte_offsets() {
+        let source = "π = \"é\" + 'ü'\n数 = π";
+        let mut lexer = Lexer::new(source);
+        let mut starts = Vec::new();
+        while let Some(token) = lexer.token() {
+            starts.push((token, lexer.token_start()));
+        }
+
+        let lexemes = ["π", "=", "\"é\"", "+", "'ü'", "\n", "数", "=", "π"];
+        assert_eq!(starts.len(), lexemes.len());
+        for ((token, start), lexeme) in starts.iter().zip(lexemes) {
+            assert!(source[*start..].starts_with(lexeme), "{:?} at {}", token, start);
+        }
+        assert_eq!(starts[6], (Token::Identifier("数".to_string()), "π = \"é\" + 'ü'\n".len()));
+    }
+
+    #[test]
+    fn warning_columns_count_characters() {
+        assert_eq!(
+            warnings("é = 9007199254740993"),
+            vec![Warning::ImpreciseNumber {
+                literal: "9007199254740993".to_string(),
+                position: Position { line: 1, column: 5 },
+            }]
+        );
+    }
+
+    #[test]
+    fn every_operator_symbol_tokenizes() {
+        let symbols = [
+            "+", "-", "*", "/", "%", "==", "===", "!=", ">", ">=", "<", "<=", "&&", "||", "!", "&", "|", "<<", ">>", "=",
+            "+=", "-=", "*=", "/=", "%=", "&&=", "||=", "<<=", ">>=", "??", "in", "is",
+        ];
+        for symbol in symbols {
+            assert_eq!(
+                tokens(&format!("a {} b", symbol)),
+                vec![
+                    Token::Identifier("a".to_string()),
+                    Token::Operator(Operator::from(symbol)),
+                    Token::Identifier("b".to_string()),
+                ],
+                "{}",
+                symbol
+            );
+        }
+    }
+
+    #[test]
+    fn large_script_tokenizes_like_its_parts() {
+        let part = "fn f(x, y) {\n  return x * 2 + y >> 1 // c\n}\ns = \"é\\n\" + 'a'\nif f(1_000, 0x1F) >= 3.5 { print s }\n";
+        let expected = tokens(part);
+        let large = tokens(&part.repeat(2000));
+
+        assert_eq!(large.len(), expected.len() * 2000);
+        assert!(large.chunks(expected.len()).all(|chunk| chunk == expected.as_slice()));
+    }
+}
@@ -1,23 +1,134 @@
 use std::fmt::Display;
-use std::ops::{Add, Sub, Mul, Div, Rem, Neg, BitAnd, BitOr};
+use std::ops::{Add, Sub, Mul, Div, Rem, Neg, BitAnd, BitOr, Shl, Shr};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::rc::Rc;
 
+use crate::parse::Function;
+
+#[allow(dead_code)]
 pub trait Object {
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Primitive {
+    /// 数値 (整数と小数を区別せず，どちらもf64で表す)
     Number(f64),
+    /// 真偽値 (+，-，*，/，%ではtrueを1，falseを0として数値と同じように計算する)
     Boolean(bool),
+    /// 文字列 (==は内容で比較し，===はRcが同一かどうかで比較する)
     String(Rc<String>),
+    Char(char),
+    Array(Rc<RefCell<Vec<Primitive>>>),
+    Function(Rc<Function>),
+    /// 複数の値をまとめたもの (複数の値のreturnに使う)
+    Tuple(Vec<Primitive>),
+    /// フィールド名と値の組 (フィールド名の順に表示する)
+    Object(Rc<RefCell<BTreeMap<String, Primitive>>>),
+    Null,
+}
+
+impl Primitive {
+    /// 真偽値として評価する
+    /// 0，空文字列，空配列，false，nullは偽，それ以外は真
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Primitive::Number(n) => *n != 0.0 && !n.is_nan(),
+            Primitive::Boolean(b) => *b,
+            Primitive::String(s) => !s.is_empty(),
+            Primitive::Char(_) => true,
+            Primitive::Array(a) => !a.borrow().is_empty(),
+            Primitive::Function(_) => true,
+            Primitive::Tuple(_) => true,
+            Primitive::Object(_) => true,
+            Primitive::Null => false,
+        }
+    }
+
+    /// 二項の算術演算で使う数値を返す
+    /// 真偽値は0か1にし，それ以外の型ならNone
+    pub fn arithmetic_operand(&self) -> Option<f64> {
+        match self {
+            Primitive::Number(n) => Some(*n),
+            Primitive::Boolean(b) => Some(f64::from(u8::from(*b))),
+            _ => None,
+        }
+    }
+
+    /// REPLでの表示や，配列，タプルの要素の表示に使う文字列を返す
+    /// printと異なり，文字列と文字を引用符で囲む
+    pub fn debug_display(&self) -> String {
+        match self {
+            Primitive::String(s) => format!("{:?}", s.as_str()),
+            Primitive::Char(c) => format!("{:?}", c),
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// 型名
+/// type_nameが返す型名の一覧
+pub const TYPE_NAMES: &[&str] = &[
+    "number", "boolean", "string", "char", "array", "function", "tuple", "object", "null",
+];
+
+pub trait TypeName {
+    fn type_name(&self) -> &'static str;
+}
+
+impl TypeName for Primitive {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Primitive::Number(_) => "number",
+            Primitive::Boolean(_) => "boolean",
+            Primitive::String(_) => "string",
+            Primitive::Char(_) => "char",
+            Primitive::Array(_) => "array",
+            Primitive::Function(_) => "function",
+            Primitive::Tuple(_) => "tuple",
+            Primitive::Object(_) => "object",
+            Primitive::Null => "null",
+        }
+    }
 }
 
 impl Display for Primitive {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Primitive::Number(n) if n.is_nan() => write!(f, "NaN"),
+            Primitive::Number(n) if n.is_infinite() => {
+                write!(f, "{}", if *n > 0.0 { "Infinity" } else { "-Infinity" })
+            }
             Primitive::Number(n) => write!(f, "{}", n),
             Primitive::Boolean(b) => write!(f, "{}", b),
             Primitive::String(s) => write!(f, "{}", s),
+            Primitive::Char(c) => write!(f, "{}", c),
+            Primitive::Array(a) => {
+                write!(f, "[")?;
+                for (i, element) in a.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element.debug_display())?;
+                }
+                write!(f, "]")
+            }
+            Primitive::Function(function) => {
+                write!(f, "fn {}({})", function.display_name(), function.parameters.join(", "))
+            }
+            Primitive::Tuple(elements) => {
+                let elements = elements.iter().map(Primitive::debug_display).collect::<Vec<_>>();
+                write!(f, "({})", elements.join(", "))
+            }
+            Primitive::Object(object) => {
+                let fields = object
+                    .borrow()
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value.debug_display()))
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+            Primitive::Null => write!(f, "null"),
         }
     }
 }
@@ -36,7 +147,10 @@ impl Add for &Primitive {
                         .into(),
                 )
             },
-            _ => panic!("invalid type"),
+            _ => match (self.arithmetic_operand(), rhs.arithmetic_operand()) {
+                (Some(l), Some(r)) => Primitive::Number(l + r),
+                _ => panic!("invalid type"),
+            },
         }
     }
 }
@@ -46,7 +160,10 @@ impl Sub for &Primitive {
     fn sub(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Primitive::Number(l), Primitive::Number(r)) => Primitive::Number(l - r),
-            _ => panic!("invalid type"),
+            _ => match (self.arithmetic_operand(), rhs.arithmetic_operand()) {
+                (Some(l), Some(r)) => Primitive::Number(l - r),
+                _ => panic!("invalid type"),
+            },
         }
     }
 }
@@ -56,7 +173,10 @@ impl Mul for &Primitive {
     fn mul(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Primitive::Number(l), Primitive::Number(r)) => Primitive::Number(l * r),
-            _ => panic!("invalid type"),
+            _ => match (self.arithmetic_operand(), rhs.arithmetic_operand()) {
+                (Some(l), Some(r)) => Primitive::Number(l * r),
+                _ => panic!("invalid type"),
+            },
         }
     }
 }
@@ -66,7 +186,10 @@ impl Div for &Primitive {
     fn div(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Primitive::Number(l), Primitive::Number(r)) => Primitive::Number(l / r),
-            _ => panic!("invalid type"),
+            _ => match (self.arithmetic_operand(), rhs.arithmetic_operand()) {
+                (Some(l), Some(r)) => Primitive::Number(l / r),
+                _ => panic!("invalid type"),
+            },
         }
     }
 }
@@ -76,7 +199,10 @@ impl Rem for &Primitive {
     fn rem(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Primitive::Number(l), Primitive::Number(r)) => Primitive::Number(l % r),
-            _ => panic!("invalid type"),
+            _ => match (self.arithmetic_operand(), rhs.arithmetic_operand()) {
+                (Some(l), Some(r)) => Primitive::Number(l % r),
+                _ => panic!("invalid type"),
+            },
         }
     }
 }
@@ -92,6 +218,27 @@ impl Neg for &Primitive {
     }
 }
 
+// シフト量は下位5ビットだけを使う
+impl Shl for &Primitive {
+    type Output = Primitive;
+    fn shl(self, rhs: Self) -> Self::Output {
+        match (self, rhs) {
+            (Primitive::Number(l), Primitive::Number(r)) => Primitive::Number((*l as i32).wrapping_shl(*r as u32) as f64),
+            _ => panic!("invalid type"),
+        }
+    }
+}
+
+impl Shr for &Primitive {
+    type Output = Primitive;
+    fn shr(self, rhs: Self) -> Self::Output {
+        match (self, rhs) {
+            (Primitive::Number(l), Primitive::Number(r)) => Primitive::Number((*l as i32).wrapping_shr(*r as u32) as f64),
+            _ => panic!("invalid type"),
+        }
+    }
+}
+
 impl BitAnd for &Primitive {
     type Output = Primitive;
     fn bitand(self, rhs: Self) -> Self::Output {
@@ -128,33 +275,9 @@ impl From<bool> for Primitive {
     }
 }
 
-pub trait LogicalAnd {
-    type Output;
-    fn logicaland(&self, rhs: &Self) -> Self::Output;
-}
-
-pub trait LogicalOr {
-    type Output;
-    fn logicalor(&self, rhs: &Self) -> Self::Output;
-}
-
-impl LogicalAnd for &Primitive{
-    type Output = Primitive;
-    fn logicaland(&self, rhs: &Self) -> Self::Output {
-        match (self, rhs) {
-            (Primitive::Boolean(l), Primitive::Boolean(r)) => Primitive::Boolean(*l && *r),
-            _ => panic!("invalid type"),
-        }
-    }
-}
-
-impl LogicalOr for &Primitive {
-    type Output = Primitive;
-    fn logicalor(&self, rhs: &Self) -> Self::Output {
-        match (self, rhs) {
-            (Primitive::Boolean(l), Primitive::Boolean(r)) => Primitive::Boolean(*l || *r),
-            _ => panic!("invalid type"),
-        }
+impl From<Vec<Primitive>> for Primitive {
+    fn from(value: Vec<Primitive>) -> Self {
+        Primitive::Array(Rc::new(RefCell::new(value)))
     }
 }
 
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::compile::{Instruction, Program};
+use crate::interpreter::{infix_operation, prefix_operation, render, short_circuits, Context, Overflow, RuntimeError};
+use crate::types::Primitive;
+
+/// バイトコードを実行する仮想機械
+pub struct Vm {
+    context: Context,
+    stack: Vec<Primitive>,
+
+    /// 1つのループで繰り返せる回数の上限 (Noneなら無制限)
+    iteration_limit: Option<usize>,
+
+    /// ビット演算，シフト演算で値が範囲を超えたときの振る舞い
+    overflow: Overflow,
+
+    /// print文で数値を表示するときの小数点以下の桁数 (Noneなら最短の表現)
+    precision: Option<usize>,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm {
+            context: Context::new(),
+            stack: Vec::new(),
+            iteration_limit: None,
+            overflow: Overflow::default(),
+            precision: None,
+        }
+    }
+
+    /// 1つのループで繰り返せる回数の上限を設定する
+    pub fn set_iteration_limit(&mut self, limit: Option<usize>) {
+        self.iteration_limit = limit;
+    }
+
+    /// ビット演算，シフト演算で値が範囲を超えたときの振る舞いを設定する
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    /// print文で数値を表示するときの小数点以下の桁数を設定する
+    pub fn set_precision(&mut self, precision: Option<usize>) {
+        self.precision = precision;
+    }
+
+    /// プログラムを実行する
+    pub fn run(&mut self, program: &Program) -> Result<(), RuntimeError> {
+        let mut iterations = vec![0; program.loops];
+        let mut pc = 0;
+
+        while let Some(instruction) = program.instructions.get(pc) {
+            pc += 1;
+
+            match instruction {
+                Instruction::Constant(value) => self.stack.push(value.clone()),
+                Instruction::Load(name) => {
+                    let value = self.context.get(name).cloned().unwrap_or(Primitive::Number(0.0));
+                    self.stack.push(value);
+                }
+                Instruction::Store(name) => {
+                    let value = self.peek().clone();
+                    self.context.assign(name, value);
+                }
+                Instruction::Pop => {
+                    self.pop();
+                }
+                Instruction::Prefix(operator) => {
+                    let right = self.pop();
+                    self.stack.push(prefix_operation(operator, &right)?);
+                }
+                Instruction::Infix(operator) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(infix_operation(operator, &left, &right, self.overflow)?);
+                }
+                Instruction::Jump(to) => pc = *to,
+                Instruction::JumpIfFalse(to) => match self.pop() {
+                    Primitive::Boolean(true) => (),
+                    Primitive::Boolean(false) => pc = *to,
+                    condition => {
+                        return Err(RuntimeError::TypeMismatch(format!(
+                            "condition must be boolean, found {}",
+                            condition
+                        )))
+                    }
+                },
+                Instruction::ShortCircuit(operator, to) => {
+                    if short_circuits(operator, self.peek()) {
+                        pc = *to;
+                    }
+                }
+                Instruction::Print(count) => {
+                    let values = self.stack.split_off(self.stack.len() - count);
+                    let values = values.iter().map(|value| render(value, self.precision)).collect::<Vec<_>>();
+                    println!("{}", values.join(" "));
+                }
+                Instruction::EnterScope => self.context.blocks.push(HashMap::new()),
+                Instruction::ExitScope => {
+                    self.context.blocks.pop();
+                }
+                Instruction::ResetIterations(counter) => iterations[*counter] = 0,
+                Instruction::CountIteration(counter) => {
+                    iterations[*counter] += 1;
+                    match self.iteration_limit {
+                        Some(limit) if iterations[*counter] > limit => {
+                            return Err(RuntimeError::IterationLimit(limit))
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn peek(&self) -> &Primitive {
+        self.stack.last().expect("stack underflow")
+    }
+
+    fn pop(&mut self) -> Primitive {
+        self.stack.pop().expect("stack underflow")
+    }
+}
@@ -0,0 +1,306 @@
+//! 実行ファイルを起動して，コマンドライン引数や終了コードを検査する
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// 一時ディレクトリにスクリプトを書き出し，そのパスを返す
+/// nameはテストごとに異なるものにする
+fn script(name: &str, code: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("simple-calc-{}-{}.calc", std::process::id(), name));
+    fs::write(&path, code).expect("failed to write script");
+    path
+}
+
+/// 引数を与えて実行する
+fn run(args: &[&str]) -> Output {
+    run_with_stdin(args, "")
+}
+
+/// 引数と標準入力を与えて実行する
+fn run_with_stdin(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_simple-calc"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start simple-calc");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().expect("failed to wait for simple-calc")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn check_accepts_valid_file_without_running_it() {
+    let path = script("check-valid", "// コメント\nprint \"ran\"\n");
+    let output = run(&["--check", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn check_reports_syntax_error() {
+    let path = script("check-invalid", "x = (1 +\n");
+    let output = run(&["--check", path.to_str().unwrap()]);
+
+    assert_ne!(output.status.code(), Some(0));
+    assert!(stderr(&output).contains("1:"), "{}", stderr(&output));
+}
+
+#[test]
+fn include_defines_functions_for_the_rest_of_the_file() {
+    let library = script("include-library", "fn double(x) { x * 2 }\n");
+    let name = library.file_name().unwrap().to_str().unwrap();
+    let path = script("include-main", &format!("include \"{}\"\nprint double(21)\n", name));
+    let output = run(&[path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "42\n");
+}
+
+#[test]
+fn include_reports_cycle() {
+    let path = std::env::temp_dir().join(format!("simple-calc-{}-include-self.calc", std::process::id()));
+    let name = path.file_name().unwrap().to_str().unwrap().to_string();
+    fs::write(&path, format!("include \"{}\"\n", name)).expect("failed to write script");
+    let output = run(&[path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("include cycle detected"), "{}", stderr(&output));
+}
+
+#[test]
+fn time_reports_duration_on_stderr() {
+    let path = script("time", "print 1 + 2\n");
+    let output = run(&["--time", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n");
+    assert!(stderr(&output).starts_with("time: "), "{}", stderr(&output));
+    assert!(stderr(&output).trim_end().ends_with(" ms"), "{}", stderr(&output));
+}
+
+#[test]
+fn time_is_not_reported_when_file_cannot_be_read() {
+    let output = run(&["--time", "/nonexistent/simple-calc.calc"]);
+
+    assert_ne!(output.status.code(), Some(0));
+    assert!(!stderr(&output).contains("time: "), "{}", stderr(&output));
+}
+
+#[test]
+fn trace_reports_evaluations_on_stderr() {
+    let path = script("trace", "print 1 + 2\n");
+    let output = run(&["--trace", path.to_str().unwrap()]);
+
+    assert_eq!(stdout(&output), "3\n");
+    assert!(stderr(&output).contains("trace: Number(1.0) => 1\n"), "{}", stderr(&output));
+}
+
+#[test]
+fn print_with_and_without_parentheses_match() {
+    let bare = run(&[script("print-bare", "a = 1\nb = \"two\"\nprint a, b\n").to_str().unwrap()]);
+    let called = run(&[script("print-called", "a = 1\nb = \"two\"\nprint(a, b)\n").to_str().unwrap()]);
+
+    assert_eq!(stdout(&bare), "1 two\n");
+    assert_eq!(stdout(&called), stdout(&bare));
+}
+
+#[test]
+fn runtime_error_exits_with_failure() {
+    let path = script("runtime-error", "print \"before\"\nerror(\"boom\")\nprint \"after\"\n");
+    let output = run(&[path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "before\n");
+    assert!(stderr(&output).contains("boom"), "{}", stderr(&output));
+}
+
+#[test]
+fn syntax_error_exits_with_failure() {
+    let output = run(&[script("syntax-error", "x = (1 +\n").to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn successful_script_exits_with_success() {
+    let output = run(&[script("success", "x = 1\n").to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn top_level_return_sets_exit_code() {
+    let zero = run(&[script("return-zero", "return 0\nprint \"unreachable\"\n").to_str().unwrap()]);
+    assert_eq!(zero.status.code(), Some(0));
+    assert_eq!(stdout(&zero), "");
+
+    let three = run(&[script("return-three", "return 3\n").to_str().unwrap()]);
+    assert_eq!(three.status.code(), Some(3));
+}
+
+#[test]
+fn top_level_return_rejects_invalid_exit_code() {
+    for (name, code) in [("return-fraction", "return 3.5\n"), ("return-large", "return 300\n")] {
+        let output = run(&[script(name, code).to_str().unwrap()]);
+
+        assert_eq!(output.status.code(), Some(1), "{}", code);
+        assert!(stderr(&output).contains("exit code must be an integer from 0 to 255"), "{}", stderr(&output));
+    }
+}
+
+#[test]
+fn repl_echo_quotes_strings_but_print_does_not() {
+    let output = run_with_stdin(&["--prompt="], "\"hello\"\nprint \"hello\"\n");
+
+    assert_eq!(stdout(&output), "\"hello\"\nhello\n");
+}
+
+#[test]
+fn vm_matches_tree_walker() {
+    let programs = [
+        "print 1 + 2 * 3, 10 / 4, 7 % 3\n",
+        "x = 2\ny = x * x\nprint x, y, -y\n",
+        "x = 5\nif x > 3 { print \"big\" } else { print \"small\" }\n",
+        "i = 0\ntotal = 0\nwhile i < 100 { total = total + i\ni = i + 1 }\nprint total\n",
+        "a = 1\nb = 0\nprint a && b, a || b, !a, a == 1, a != 1\n",
+        "print 1 && \"x\", \"\" || null, null && 1, 0 || \"y\", true && false, false || true\n",
+    ];
+
+    for (i, code) in programs.iter().enumerate() {
+        let path = script(&format!("vm-{}", i), code);
+        let tree_walker = run(&[path.to_str().unwrap()]);
+        let vm = run(&["--vm", "-W", path.to_str().unwrap()]);
+
+        assert_eq!(stderr(&vm), "", "{}", code);
+        assert_eq!(stdout(&vm), stdout(&tree_walker), "{}", code);
+    }
+}
+
+#[test]
+fn eval_prints_value_of_single_expression() {
+    let output = run(&["--eval", "1 + 2 * 3"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "7\n");
+}
+
+#[test]
+fn eval_prints_value_with_precision() {
+    let output = run(&["--precision=2", "--eval", "1 / 3"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "0.33\n");
+
+    let output = run(&["--vm", "--precision=2", "--eval", "[2 / 3]"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "[0.67]\n");
+}
+
+#[test]
+fn eval_runs_statements_without_printing_value() {
+    let output = run(&["--eval", "x = 4\nprint x * 2\nx"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "8\n");
+}
+
+#[test]
+fn quiet_repl_prints_only_explicit_output() {
+    let output = run_with_stdin(&["--quiet"], "x = 2\nx + 1\nprint x * 10\n");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "20\n");
+}
+
+#[test]
+fn repl_uses_configured_prompt() {
+    let output = run_with_stdin(&["--prompt=? "], "1 + 1\n");
+
+    assert_eq!(stdout(&output), "? 2\n? ");
+}
+
+#[test]
+fn ast_json_prints_syntax_tree() {
+    let path = script("ast-json", "x = 1 + 2\nprint true\n");
+    let output = run(&["--ast-json", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        concat!(
+            r#"[{"type":"Expr","expr":{"type":"InfixExpr","left":{"type":"Identifier","name":"x"},"operator":"Assign","#,
+            r#""right":{"type":"InfixExpr","left":{"type":"Number","value":1},"operator":"Plus","right":{"type":"Number","value":2}}}},"#,
+            r#"{"type":"Print","arguments":[{"type":"Boolean","value":true}]}]"#,
+            "\n"
+        )
+    );
+}
+
+#[test]
+fn ast_json_reports_syntax_error() {
+    let output = run(&["--ast-json", script("ast-json-invalid", "x = (\n").to_str().unwrap()]);
+
+    assert_ne!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn eprint_writes_to_stderr() {
+    let output = run(&[script("eprint", "print \"out\"\neprint \"err\"\n").to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "out\n");
+    assert_eq!(stderr(&output), "err\n");
+}
+
+#[test]
+fn optimized_output_matches_unoptimized_output() {
+    let programs = [
+        "pure fn d(x) { x * 2 }\nprint d(5)\nd = fn(x) { x * 3 }\nprint d(5)\n",
+        "pure fn e(x) { x + 1 }\nif true {\nfn e(x) { x + 100 }\nprint e(1)\n}\nprint e(1)\n",
+        "pure fn sq(x) { x * x }\nfn f(sq) { sq(3) }\nprint sq(4), f(fn(x) { -x })\n",
+        "print 1 + 2 * 3, \"a\" + \"b\", 0 && 1, 1 || 0\n",
+    ];
+
+    for (i, code) in programs.iter().enumerate() {
+        let path = script(&format!("optimize-{}", i), code);
+        let unoptimized = run(&[path.to_str().unwrap()]);
+        let optimized = run(&["-O", path.to_str().unwrap()]);
+
+        assert_eq!(stdout(&optimized), stdout(&unoptimized), "{}", code);
+        assert_eq!(stderr(&optimized), stderr(&unoptimized), "{}", code);
+    }
+}
+
+#[test]
+fn main_return_value_sets_exit_code() {
+    let output = run(&[script("main-three", "fn main() {\nprint \"in main\"\nreturn 3\n}\n").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "in main\n");
+
+    let output = run(&[script("main-null", "fn main() {}\n").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn main_rejects_invalid_exit_code() {
+    for (name, value) in [("main-fraction", "3.5"), ("main-large", "256"), ("main-negative", "-1"), ("main-string", "\"x\"")] {
+        let output = run(&[script(name, &format!("fn main() {{ return {} }}\n", value)).to_str().unwrap()]);
+
+        assert_eq!(output.status.code(), Some(1), "{}", value);
+        assert!(stderr(&output).contains("exit code must be an integer from 0 to 255"), "{}", stderr(&output));
+    }
+}